@@ -0,0 +1,154 @@
+//! Compares writing a record to several targets with a shared rendering against rendering it once per target.
+//!
+//! Run with `cargo bench --bench fanout`.
+
+use std::{
+	hint::black_box,
+	io::{self, Write},
+	time::{Duration, Instant}
+};
+
+use edwardium_logger::{
+	target::{Target, TargetResults, Targets},
+	targets::util::{
+		layout::{Layout, LayoutStyle},
+		LineFields,
+		LogLine,
+		TimestampFormat
+	}
+};
+use log::{Level, Record};
+
+const RECORDS: u32 = 200_000;
+
+const LAYOUT_FIELDS: LineFields = LineFields {
+	location: true,
+	module_path: true,
+	..LineFields::NONE
+};
+
+/// Writer discarding everything written to it.
+///
+/// Unlike `io::sink`, whose `write_fmt` does not format its arguments at all, it makes `writeln!` render the whole line.
+struct Discard;
+impl Write for Discard {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		Ok(black_box(buf).len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Target discarding lines rendered through a shared layout.
+struct SharedSink;
+impl Target for SharedSink {
+	type Error = io::Error;
+
+	fn level(&self) -> Level {
+		Level::Trace
+	}
+
+	fn write(&self, duration_since_start: Duration, record: &Record) -> io::Result<()> {
+		let layout = self.layout(duration_since_start).unwrap();
+		layout.render(record, &mut Discard)
+	}
+
+	fn layout(&self, duration_since_start: Duration) -> Option<Layout<'_>> {
+		Some(Layout {
+			style: LayoutStyle::Plain,
+			timestamp: duration_since_start,
+			timestamp_format: TimestampFormat::DEFAULT,
+			fields: LAYOUT_FIELDS
+		})
+	}
+
+	fn write_rendered(
+		&self,
+		_duration_since_start: Duration,
+		_record: &Record,
+		line: &[u8]
+	) -> io::Result<()> {
+		io::sink().write_all(black_box(line))
+	}
+
+	fn flush(&self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Target discarding lines it renders by itself.
+struct OwnSink;
+impl Target for OwnSink {
+	type Error = io::Error;
+
+	fn level(&self) -> Level {
+		Level::Trace
+	}
+
+	fn write(&self, duration_since_start: Duration, record: &Record) -> io::Result<()> {
+		writeln!(
+			Discard,
+			"{}",
+			LogLine::new(duration_since_start.into(), record).with_fields(LAYOUT_FIELDS, record)
+		)
+	}
+
+	fn flush(&self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+fn bench(name: &str, targets: &impl Targets) {
+	let start = Instant::now();
+	for index in 0 .. RECORDS {
+		let float = index as f64 / 7.0;
+		targets
+			.write(
+				Duration::from_micros(index as u64),
+				&Record::builder()
+					.level(Level::Info)
+					.target("bench::fanout")
+					.module_path(Some("bench::fanout"))
+					.file(Some("benches/fanout.rs"))
+					.line(Some(index))
+					.args(format_args!(
+						"record number {} with a float {:.3}",
+						index, float
+					))
+					.build()
+			)
+			.log_errors(|err| panic!("{}", err));
+	}
+	let elapsed = start.elapsed();
+
+	println!(
+		"{:<24} {:>8.1} ns/record",
+		name,
+		elapsed.as_nanos() as f64 / RECORDS as f64
+	);
+}
+
+fn main() {
+	bench("1 target, shared", &(SharedSink,));
+	bench("1 target, own", &(OwnSink,));
+	bench(
+		"4 targets, shared",
+		&(SharedSink, SharedSink, SharedSink, SharedSink)
+	);
+	bench("4 targets, own", &(OwnSink, OwnSink, OwnSink, OwnSink));
+	bench(
+		"8 targets, shared",
+		&(
+			SharedSink, SharedSink, SharedSink, SharedSink, SharedSink, SharedSink, SharedSink,
+			SharedSink
+		)
+	);
+	bench(
+		"8 targets, own",
+		&(
+			OwnSink, OwnSink, OwnSink, OwnSink, OwnSink, OwnSink, OwnSink, OwnSink
+		)
+	);
+}
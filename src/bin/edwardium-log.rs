@@ -0,0 +1,318 @@
+//! Viewer for log files written by `FileTarget`.
+//!
+//! Reads the given files in order (for example `app.log.1 app.log`), filters the entries and prints them,
+//! optionally following the last file as it grows and across rotations.
+
+use std::{
+	fs::File,
+	io::{self, BufRead, BufReader, IsTerminal, Read, Seek, Write},
+	path::{Path, PathBuf},
+	process,
+	str::FromStr,
+	thread,
+	time::Duration
+};
+
+use edwardium_logger::{
+	parse::{parse_timestamp, LogEntry, LogReader, ParseError},
+	targets::{
+		file::file_id,
+		util::{
+			colored_logline::{ColorMode, ColoredLogLine},
+			Timestamp
+		}
+	}
+};
+use log::LevelFilter;
+
+const USAGE: &str = "Usage: edwardium-log [OPTIONS] [FILE]...
+
+Reads log files written by edwardium_logger FileTarget, stdin if no file is given.
+
+Options:
+  -l, --level <LEVEL>      Show only entries at or above the severity of LEVEL (error, warn, info, debug, trace)
+  -t, --target <PATTERN>   Show only entries whose target contains PATTERN, can be repeated
+      --since <TIME>       Show only entries logged at or after TIME since start
+      --until <TIME>       Show only entries logged at or before TIME since start
+  -f, --follow             Keep reading the last file as it grows, reopening it when it is rotated
+      --color <WHEN>       Colorize the output: auto, always, never [default: auto], auto honors NO_COLOR and CLICOLOR_FORCE
+  -h, --help               Print this help
+
+TIME is either in the log timestamp format (mmm:ss.mmmm) or a number with a unit suffix (ms, s, m, h).";
+
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+
+struct Filter {
+	level: LevelFilter,
+	targets: Vec<String>,
+	since: Option<Duration>,
+	until: Option<Duration>
+}
+impl Filter {
+	fn matches(&self, entry: &LogEntry) -> bool {
+		entry.level <= self.level
+			&& (self.targets.is_empty()
+				|| self
+					.targets
+					.iter()
+					.any(|t| entry.target.contains(t.as_str())))
+			&& !matches!(self.since, Some(since) if entry.duration_since_start < since)
+			&& !matches!(self.until, Some(until) if entry.duration_since_start > until)
+	}
+}
+
+struct Options {
+	filter: Filter,
+	follow: bool,
+	color: bool,
+	files: Vec<PathBuf>
+}
+
+fn parse_time(value: &str) -> Option<Duration> {
+	if let Some(duration) = parse_timestamp(value) {
+		return Some(duration)
+	}
+
+	let (number, unit) = value.split_at(value.find(|c: char| c.is_ascii_alphabetic())?);
+	let number = f64::from_str(number).ok()?;
+	let seconds = match unit {
+		"ms" => number / 1000.0,
+		"s" => number,
+		"m" => number * 60.0,
+		"h" => number * 3600.0,
+		_ => return None
+	};
+
+	Duration::try_from_secs_f64(seconds).ok()
+}
+
+fn parse_args() -> Result<Options, String> {
+	let mut options = Options {
+		filter: Filter {
+			level: LevelFilter::Trace,
+			targets: Vec::new(),
+			since: None,
+			until: None
+		},
+		follow: false,
+		color: ColorMode::Auto.enabled(|| io::stdout().is_terminal()),
+		files: Vec::new()
+	};
+
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = |name: &str| {
+			args.next()
+				.ok_or_else(|| format!("missing value for {}", name))
+		};
+
+		match arg.as_str() {
+			"-h" | "--help" => {
+				println!("{}", USAGE);
+				process::exit(0);
+			}
+			"-l" | "--level" => {
+				let level = value(&arg)?;
+				options.filter.level = LevelFilter::from_str(&level)
+					.map_err(|_| format!("invalid level {}", level))?;
+			}
+			"-t" | "--target" => options.filter.targets.push(value(&arg)?),
+			"--since" | "--until" => {
+				let time = value(&arg)?;
+				let time = parse_time(&time).ok_or_else(|| format!("invalid time {}", time))?;
+				if arg == "--since" {
+					options.filter.since = Some(time);
+				} else {
+					options.filter.until = Some(time);
+				}
+			}
+			"-f" | "--follow" => options.follow = true,
+			"--color" => {
+				let color_mode = match value(&arg)?.as_str() {
+					"auto" => ColorMode::Auto,
+					"always" => ColorMode::Always,
+					"never" => ColorMode::Never,
+					other => return Err(format!("invalid color mode {}", other))
+				};
+				options.color = color_mode.enabled(|| io::stdout().is_terminal());
+			}
+			_ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+			_ => options.files.push(PathBuf::from(arg))
+		}
+	}
+
+	Ok(options)
+}
+
+/// Filters and prints entries.
+struct Printer<W: Write> {
+	output: W,
+	filter: Filter,
+	color: bool,
+	/// Whether the last entry was printed, its late continuation lines are printed as well.
+	last_printed: bool
+}
+impl<W: Write> Printer<W> {
+	/// Prints all entries `reader` yields until the end of its input.
+	fn print(&mut self, reader: &mut LogReader<impl BufRead>) -> io::Result<()> {
+		for entry in reader {
+			match entry {
+				Ok(entry) => self.entry(&entry)?,
+				Err(ParseError::UnexpectedLine { line, .. }) => {
+					if self.last_printed {
+						writeln!(self.output, "{}", line)?;
+					}
+				}
+				Err(ParseError::Io(err)) => return Err(err)
+			}
+		}
+
+		Ok(())
+	}
+
+	fn entry(&mut self, entry: &LogEntry) -> io::Result<()> {
+		self.last_printed = self.filter.matches(entry);
+		if !self.last_printed {
+			return Ok(())
+		}
+
+		if self.color {
+			let timestamp =
+				Timestamp::from(entry.duration_since_start).with_format(entry.timestamp_format);
+			let args = format_args!("{}", entry.message);
+			let line = ColoredLogLine::from_parts(timestamp, entry.level, &entry.target, &args)
+				.with_parsed_fields(&entry.fields);
+			writeln!(self.output, "{}", line)
+		} else {
+			writeln!(self.output, "{}", entry)
+		}
+	}
+}
+
+/// `BufRead` over a file that is still being written to, which only yields complete lines.
+///
+/// An incomplete last line is kept until the rest of it is written.
+struct CompleteLines<R: Read> {
+	inner: R,
+	buffer: Vec<u8>,
+	consumed: usize
+}
+impl<R: Read> CompleteLines<R> {
+	fn new(inner: R) -> Self {
+		CompleteLines {
+			inner,
+			buffer: Vec::new(),
+			consumed: 0
+		}
+	}
+}
+impl<R: Read> Read for CompleteLines<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let available = self.fill_buf()?;
+		let len = available.len().min(buf.len());
+		buf[.. len].copy_from_slice(&available[.. len]);
+		self.consume(len);
+
+		Ok(len)
+	}
+}
+impl<R: Read> BufRead for CompleteLines<R> {
+	fn fill_buf(&mut self) -> io::Result<&[u8]> {
+		if !self.buffer[self.consumed ..].contains(&b'\n') {
+			self.buffer.drain(.. self.consumed);
+			self.consumed = 0;
+
+			let mut chunk = [0; 8192];
+			loop {
+				let read = self.inner.read(&mut chunk)?;
+				self.buffer.extend_from_slice(&chunk[.. read]);
+				if read == 0 || chunk[.. read].contains(&b'\n') {
+					break
+				}
+			}
+		}
+
+		let available = &self.buffer[self.consumed ..];
+		let end = available
+			.iter()
+			.rposition(|&b| b == b'\n')
+			.map_or(0, |index| index + 1);
+
+		Ok(&available[.. end])
+	}
+
+	fn consume(&mut self, amount: usize) {
+		self.consumed += amount;
+	}
+}
+
+fn follow(printer: &mut Printer<impl Write>, path: &Path, file: File) -> io::Result<()> {
+	let mut id = file_id(&file.metadata()?);
+	let mut reader = LogReader::new(CompleteLines::new(file));
+
+	loop {
+		printer.print(&mut reader)?;
+		printer.output.flush()?;
+		thread::sleep(FOLLOW_INTERVAL);
+
+		let position = reader.get_mut().inner.stream_position()?;
+		let rotated = match std::fs::metadata(path) {
+			// the file is being rotated, wait for the new one
+			Err(_) => continue,
+			Ok(metadata) => file_id(&metadata) != id || metadata.len() < position
+		};
+
+		if rotated {
+			// read whatever was written to the old file before it was rotated
+			printer.print(&mut reader)?;
+
+			let file = File::open(path)?;
+			id = file_id(&file.metadata()?);
+			reader = LogReader::new(CompleteLines::new(file));
+		}
+	}
+}
+
+fn run(options: Options) -> io::Result<()> {
+	let stdout = io::stdout();
+	let mut printer = Printer {
+		output: stdout.lock(),
+		filter: options.filter,
+		color: options.color,
+		last_printed: false
+	};
+
+	if options.files.is_empty() {
+		printer.print(&mut LogReader::new(io::stdin().lock()))?;
+	}
+
+	for (index, path) in options.files.iter().enumerate() {
+		let file = File::open(path)
+			.map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+
+		if options.follow && index + 1 == options.files.len() {
+			return follow(&mut printer, path, file)
+		}
+
+		printer.print(&mut LogReader::new(BufReader::new(file)))?;
+	}
+
+	printer.output.flush()
+}
+
+fn main() {
+	let options = match parse_args() {
+		Ok(options) => options,
+		Err(err) => {
+			eprintln!("edwardium-log: {}\n\n{}", err, USAGE);
+			process::exit(2);
+		}
+	};
+
+	if let Err(err) = run(options) {
+		if err.kind() != io::ErrorKind::BrokenPipe {
+			eprintln!("edwardium-log: {}", err);
+			process::exit(1);
+		}
+	}
+}
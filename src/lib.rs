@@ -5,7 +5,8 @@
 //! A target has to implement the [`Target`](target/trait.Target.html) trait.
 //!
 //! To start logging, create the [`Logger`](struct.Logger.html) object either statically or dynamically
-//! and then call one of its `init_` methods.
+//! and then call one of its `init_` methods. The logger holds a [`Clock`](timing/trait.Clock.html) which provides
+//! the time since start of each record.
 //!
 //! For example, for a dynamic logger (requires std feature):
 //!
@@ -18,20 +19,43 @@
 //! logger.init_boxed().expect("Could not initialize logger");
 //! ```
 //!
-//! Logger can also be created and set statically, though this has a few caveats (read the documentation of [`Logger::new`](struct.Logger.html#method.new) for more):
+//! Logger can also be created and set statically using the [`static_logger!`](macro.static_logger.html) macro,
+//! which spells out the type of the static from the listed targets:
 //!
 //! ```
 //! use edwardium_logger::{
 //! 	targets::{stderr::StderrTarget, util::ignore_list::IgnoreList},
 //! 	timing::DummyTiming
 //! };
-//! static LOGGER: edwardium_logger::Logger<(StderrTarget), DummyTiming> =
-//! 	edwardium_logger::Logger {
-//! 		targets: StderrTarget::new(log::Level::Trace, IgnoreList::EMPTY_PATTERNS),
-//! 		start: DummyTiming
-//! 	};
-//! LOGGER.init_static();
+//! edwardium_logger::static_logger!(
+//! 	DummyTiming = DummyTiming;
+//! 	StderrTarget = StderrTarget::new(log::Level::Trace, IgnoreList::EMPTY_PATTERNS)
+//! )
+//! .expect("Could not initialize logger");
 //! ```
+//!
+//! or by hand, since [`Logger::new`](struct.Logger.html#method.new) is `const`:
+//!
+//! ```
+//! use edwardium_logger::{
+//! 	targets::{stderr::StderrTarget, util::ignore_list::IgnoreList},
+//! 	timing::LazyInstant,
+//! 	Logger
+//! };
+//! static LOGGER: Logger<(StderrTarget,), LazyInstant> = Logger::new(
+//! 	(StderrTarget::new(
+//! 		log::Level::Trace,
+//! 		IgnoreList::EMPTY_PATTERNS
+//! 	),),
+//! 	LazyInstant::new()
+//! );
+//! LOGGER.init_static().expect("Could not initialize logger");
+//! ```
+//!
+//! The UART targets of the `uart_target` feature lock their sink using [`critical-section`](https://docs.rs/critical-section),
+//! so the final binary has to provide a critical section implementation, also when building with `std`
+//! (for example by enabling the `std` feature of `critical-section`). Without one linking fails with
+//! undefined `_critical_section_1_0_acquire` and `_critical_section_1_0_release` symbols.
 
 // TODO: Not really tested
 // This crate also has a `no_std` (disable default features) version and
@@ -39,103 +63,260 @@
 // under the `uart_target` feature.
 
 #![cfg_attr(not(feature = "std"), no_std)]
+#![allow(clippy::tabs_in_doc_comments)]
 
 #[cfg(not(feature = "std"))]
-use core as std;
+extern crate core as std;
 
 use log::{Log, Metadata, Record, SetLoggerError};
 
+mod macros;
+
 pub mod target;
 pub mod timing;
 
+#[cfg(feature = "std")]
+pub mod parse;
+
 pub mod targets;
 
 use target::TargetResults;
 
 /// Logger
-///
-/// TODO: The fields of this struct are public only because generics on const fns are unstable.
 pub struct Logger<Targ, Time>
 where
 	Targ: target::Targets + Send + Sync + 'static,
-	Time: timing::Timing + Send + Sync + 'static
+	Time: timing::Clock + Send + Sync + 'static
 {
-	pub targets: Targ,
-	pub start: Time
+	targets: Targ,
+	clock: Time
 }
 impl<Targ, Time> Logger<Targ, Time>
 where
 	Targ: target::Targets + Send + Sync + 'static,
-	Time: timing::Timing + Send + Sync + 'static
+	Time: timing::Clock + Send + Sync + 'static
 {
 	/// Creates a new Logger.
 	///
-	/// TODO: This function should be `const` but that is unstable with generic parameters, thus the fields of the logger are made public instead.
-	pub fn new(targets: Targ, start: Time) -> Self {
-		Logger { targets, start }
+	/// This function is `const`, so the logger can be stored in a `static` as long as the targets and the clock
+	/// can be created in a `const` context.
+	pub const fn new(targets: Targ, clock: Time) -> Self {
+		Logger { targets, clock }
 	}
 
-	/// Returns a reference to the `start` field.
-	pub fn start(&self) -> &Time {
-		&self.start
+	/// Returns a reference to the targets.
+	pub fn targets(&self) -> &Targ {
+		&self.targets
 	}
 
-	/// Returns a mutable reference to the `start` field.
+	/// Returns a reference to the clock.
+	pub fn clock(&self) -> &Time {
+		&self.clock
+	}
+
+	/// Returns a mutable reference to the clock.
 	///
-	/// This can be used to have a `static mut` logger and change the `start` at the beginning of the program.
+	/// This can be used to have a `static mut` logger and change the clock at the beginning of the program.
+	/// With `std`, a static logger can use [`LazyInstant`](timing/struct.LazyInstant.html) instead.
+	pub fn clock_mut(&mut self) -> &mut Time {
+		&mut self.clock
+	}
+
+	#[deprecated(note = "renamed to `clock`")]
+	pub fn start(&self) -> &Time {
+		&self.clock
+	}
+
+	#[deprecated(note = "renamed to `clock_mut`")]
 	pub fn start_mut(&mut self) -> &mut Time {
-		&mut self.start
+		&mut self.clock
 	}
 
-	#[cfg(feature = "std")]
+	#[cfg(all(feature = "std", target_has_atomic = "ptr"))]
 	pub fn init_boxed(self) -> Result<(), SetLoggerError> {
 		let max_level = self.targets.max_level();
 		log::set_max_level(max_level);
+		self.clock.init();
 
 		let logger = Box::new(self);
 		log::set_boxed_logger(logger)?;
 		Ok(())
 	}
 
-	// TODO: On thumbv6 this won't compile
+	/// Initializes the logger like [`init_boxed`](#method.init_boxed) and returns a guard that shuts down
+	/// the targets when dropped.
+	///
+	/// ```
+	/// use edwardium_logger::targets::stderr::StderrTarget;
+	///
+	/// fn main() {
+	/// 	let _guard = edwardium_logger::Logger::new(
+	/// 		StderrTarget::new(log::Level::Trace, Default::default()),
+	/// 		std::time::Instant::now()
+	/// 	)
+	/// 	.init_boxed_guarded()
+	/// 	.expect("Could not initialize logger");
+	///
+	/// 	log::info!("the last record is flushed at the end of main");
+	/// }
+	/// ```
+	#[cfg(all(feature = "std", target_has_atomic = "ptr"))]
+	pub fn init_boxed_guarded(self) -> Result<LoggerGuard<Targ, Time>, SetLoggerError> {
+		let logger: &'static Self = Box::leak(Box::new(self));
+		logger.init_static()?;
+
+		Ok(LoggerGuard { logger })
+	}
+
+	/// Initializes the logger like [`init_static`](#method.init_static) and returns a guard that shuts down
+	/// the targets when dropped.
+	#[cfg(target_has_atomic = "ptr")]
+	pub fn init_static_guarded(&'static self) -> Result<LoggerGuard<Targ, Time>, SetLoggerError> {
+		self.init_static()?;
+
+		Ok(LoggerGuard { logger: self })
+	}
+
+	/// Initializes the logger.
+	///
+	/// Only available on targets with atomic compare-and-swap, on others see
+	/// [`init_static_cs`](#method.init_static_cs) or [`init_static_racy`](#method.init_static_racy).
+	#[cfg(target_has_atomic = "ptr")]
 	pub fn init_static(&'static self) -> Result<(), SetLoggerError> {
 		let max_level = self.targets.max_level();
 		log::set_max_level(max_level);
+		self.clock.init();
 
 		log::set_logger(self)?;
 		Ok(())
 	}
 
+	/// Initializes the logger using `log::set_logger_racy`.
+	///
+	/// # Safety
+	///
+	/// See [`log::set_logger_racy`]: this function must not be called concurrently with any other logger initialization.
 	pub unsafe fn init_static_racy(&'static self) -> Result<(), SetLoggerError> {
 		let max_level = self.targets.max_level();
+		#[cfg(target_has_atomic = "ptr")]
 		log::set_max_level(max_level);
+		#[cfg(not(target_has_atomic = "ptr"))]
+		log::set_max_level_racy(max_level);
+		self.clock.init();
 
 		log::set_logger_racy(self)?;
 		Ok(())
 	}
 
+	/// Initializes the logger inside a critical section.
+	///
+	/// Safe alternative to [`init_static_racy`](#method.init_static_racy) for targets without atomic compare-and-swap,
+	/// such as Cortex-M0, see [`init_static_in`](#method.init_static_in).
+	///
+	/// Requires the `critical_section_init` feature and a `critical-section` implementation, for example the one
+	/// provided by `cortex-m` with its `critical-section-single-core` feature.
+	#[cfg(feature = "critical_section_init")]
+	pub fn init_static_cs(&'static self) -> Result<(), SetLoggerError> {
+		critical_section::with(|cs| self.init_static_in(cs))
+	}
+
+	/// Initializes the logger while already inside a critical section.
+	///
+	/// On targets with atomic compare-and-swap this is [`init_static`](#method.init_static). On others the logger
+	/// is set using `log::set_logger_racy`, the critical section only excludes other code holding one, so any
+	/// other logger initialization must also happen inside a critical section.
+	///
+	/// ```
+	/// use edwardium_logger::{
+	/// 	targets::{stderr::StderrTarget, util::ignore_list::IgnoreList},
+	/// 	timing::DummyTiming,
+	/// 	Logger
+	/// };
+	/// static LOGGER: Logger<StderrTarget, DummyTiming> = Logger::new(
+	/// 	StderrTarget::new(log::Level::Trace, IgnoreList::EMPTY_PATTERNS),
+	/// 	DummyTiming
+	/// );
+	///
+	/// critical_section::with(|cs| LOGGER.init_static_in(cs)).expect("Could not initialize logger");
+	/// ```
+	#[cfg(feature = "critical_section_init")]
+	pub fn init_static_in(
+		&'static self,
+		_cs: critical_section::CriticalSection
+	) -> Result<(), SetLoggerError> {
+		#[cfg(target_has_atomic = "ptr")]
+		return self.init_static();
+
+		// SAFETY: without compare-and-swap `log` only offers racy initialization, which other initializations
+		// are excluded from by holding a critical section as documented above
+		#[cfg(not(target_has_atomic = "ptr"))]
+		unsafe {
+			self.init_static_racy()
+		}
+	}
+
 	#[cfg(feature = "std")]
 	fn on_error(&self, error: &dyn std::fmt::Display) {
 		eprintln!("{}", error);
 	}
 
 	#[cfg(not(feature = "std"))]
-	fn on_error(&self, error: &dyn core::fmt::Display) {
+	fn on_error(&self, _error: &dyn core::fmt::Display) {
 		// Nothing to do?
 	}
 }
+/// Guard returned by the `init_*_guarded` methods of [`Logger`](struct.Logger.html).
+///
+/// Dropping the guard flushes all targets and stops their background work, so that the last records are not lost
+/// at the end of `main`. Note that `std::process::exit` doesn't run destructors, call [`shutdown`](#method.shutdown)
+/// explicitly before exiting.
+#[must_use = "dropping the guard immediately shuts the targets down"]
+pub struct LoggerGuard<Targ, Time>
+where
+	Targ: target::Targets + Send + Sync + 'static,
+	Time: timing::Clock + Send + Sync + 'static
+{
+	logger: &'static Logger<Targ, Time>
+}
+impl<Targ, Time> LoggerGuard<Targ, Time>
+where
+	Targ: target::Targets + Send + Sync + 'static,
+	Time: timing::Clock + Send + Sync + 'static
+{
+	/// Returns the guarded logger.
+	pub fn logger(&self) -> &'static Logger<Targ, Time> {
+		self.logger
+	}
+
+	/// Shuts the targets down now instead of when the guard goes out of scope.
+	pub fn shutdown(self) {}
+}
+impl<Targ, Time> Drop for LoggerGuard<Targ, Time>
+where
+	Targ: target::Targets + Send + Sync + 'static,
+	Time: timing::Clock + Send + Sync + 'static
+{
+	fn drop(&mut self) {
+		let results = self.logger.targets.shutdown();
+		results.log_errors(|err| self.logger.on_error(err));
+	}
+}
+
 impl<Targ, Time> Log for Logger<Targ, Time>
 where
 	Targ: target::Targets + Send + Sync + 'static,
-	Time: timing::Timing + Send + Sync + 'static
+	Time: timing::Clock + Send + Sync + 'static
 {
 	fn enabled(&self, metadata: &Metadata) -> bool {
-		self.targets.max_level() >= metadata.level()
+		self.targets.enabled(metadata)
 	}
 
 	fn log(&self, record: &Record) {
-		let now = Time::now();
-		let duration_since_start = now.duration_since(&self.start);
+		if !self.targets.enabled(record.metadata()) {
+			return
+		}
+
+		let duration_since_start = self.clock.elapsed();
 
 		let results = self.targets.write(duration_since_start, record);
 		results.log_errors(|err| self.on_error(err));
@@ -146,3 +327,29 @@ where
 		results.log_errors(|err| self.on_error(err));
 	}
 }
+
+#[cfg(all(test, feature = "stderr_target"))]
+mod tests {
+	use std::borrow::Cow;
+
+	use log::{Level, Log, Metadata};
+
+	use crate::{targets::stderr::StderrTarget, timing::DummyTiming, Logger};
+
+	#[test]
+	fn ignore_list_disables_records() {
+		let logger = Logger::new(
+			StderrTarget::new(Level::Trace, Cow::Borrowed(&[Cow::Borrowed("noisy")])),
+			DummyTiming
+		);
+		let metadata = |target| {
+			Metadata::builder()
+				.level(Level::Error)
+				.target(target)
+				.build()
+		};
+
+		assert!(!logger.enabled(&metadata("app::noisy")));
+		assert!(logger.enabled(&metadata("app")));
+	}
+}
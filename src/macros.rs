@@ -0,0 +1,60 @@
+/// Declares a static logger from a clock and a list of targets.
+///
+/// Each target is given as `Type = expression`, the logger type is built from the listed types.
+/// All expressions must be usable in a `const` context.
+///
+/// Declaring a static:
+///
+/// ```
+/// use edwardium_logger::{
+/// 	targets::{stderr::StderrTarget, stdout::StdoutTarget, util::ignore_list::IgnoreList},
+/// 	timing::LazyInstant
+/// };
+///
+/// edwardium_logger::static_logger! {
+/// 	static LOGGER: LazyInstant = LazyInstant::new();
+/// 	StdoutTarget = StdoutTarget::new(log::Level::Info, IgnoreList::EMPTY_PATTERNS),
+/// 	StderrTarget = StderrTarget::new(log::Level::Warn, IgnoreList::EMPTY_PATTERNS)
+/// }
+///
+/// LOGGER.init_static().expect("Could not initialize logger");
+/// ```
+///
+/// Without the `static` the macro declares a hidden static and initializes it, returning the result of `init_static`:
+///
+/// ```
+/// use edwardium_logger::{
+/// 	targets::{stderr::StderrTarget, util::ignore_list::IgnoreList},
+/// 	timing::LazyInstant
+/// };
+///
+/// edwardium_logger::static_logger!(
+/// 	LazyInstant = LazyInstant::new();
+/// 	StderrTarget = StderrTarget::new(log::Level::Trace, IgnoreList::EMPTY_PATTERNS)
+/// )
+/// .expect("Could not initialize logger");
+/// ```
+#[macro_export]
+macro_rules! static_logger {
+	(
+		$( #[$attr: meta] )*
+		$vis: vis static $name: ident: $clock_type: ty = $clock: expr;
+		$( $target_type: ty = $target: expr ),+ $(,)?
+	) => {
+		$( #[$attr] )*
+		$vis static $name: $crate::Logger<( $( $target_type, )+ ), $clock_type> =
+			$crate::Logger::new(( $( $target, )+ ), $clock);
+	};
+
+	(
+		$clock_type: ty = $clock: expr;
+		$( $target_type: ty = $target: expr ),+ $(,)?
+	) => {{
+		$crate::static_logger! {
+			static LOGGER: $clock_type = $clock;
+			$( $target_type = $target ),+
+		}
+
+		LOGGER.init_static()
+	}};
+}
@@ -0,0 +1,406 @@
+//! Parser for the line format produced by [`LogLine`](../targets/util/struct.LogLine.html).
+//!
+//! Each entry starts with a header `[+mmm:ss.mmmm][LEVEL] (target) message` (or any other
+//! [`parse_timestamp`](fn.parse_timestamp.html) layout), optionally with
+//! [`LineFields`](../targets/util/struct.LineFields.html) segments `[key=value]` after the level. Lines that don't
+//! start with a header are continuation lines of a multi-line message and are appended to the previous entry.
+//!
+//! ```
+//! use edwardium_logger::parse::LogReader;
+//!
+//! let input = "[+000:01.0025][INFO] (app) started\n[+001:00.0000][ERROR] (app::db) query failed:\n  timeout\n";
+//! let entries: Vec<_> = LogReader::new(input.as_bytes()).collect::<Result<_, _>>().unwrap();
+//!
+//! assert_eq!(entries.len(), 2);
+//! assert_eq!(entries[0].duration_since_start, std::time::Duration::from_millis(1025));
+//! assert_eq!(entries[1].level, log::Level::Error);
+//! assert_eq!(entries[1].target, "app::db");
+//! assert_eq!(entries[1].message, "query failed:\n  timeout");
+//! assert_eq!(entries[0].to_string(), "[+000:01.0025][INFO] (app) started");
+//!
+//! let entry: edwardium_logger::parse::LogEntry = "[+000:00.0000][WARN][thread=main][pid=7] (app) slow".parse().unwrap();
+//! assert_eq!(entry.fields[0], ("thread".to_string(), "main".to_string()));
+//! assert_eq!(entry.message, "slow");
+//! ```
+
+use std::{
+	fmt::{self, Display, Formatter},
+	io::{self, BufRead},
+	str::FromStr,
+	time::Duration
+};
+
+use log::Level;
+
+use crate::targets::util::{LargestUnit, LogLine, Precision, Timestamp, TimestampFormat};
+
+/// Structured log entry.
+///
+/// Displays in the same format it was parsed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+	pub duration_since_start: Duration,
+	/// Format the timestamp was written in, the entry displays its timestamp in the same format.
+	pub timestamp_format: TimestampFormat,
+	pub level: Level,
+	/// Optional `key=value` fields in the order they appeared.
+	pub fields: Vec<(String, String)>,
+	pub target: String,
+	pub message: String
+}
+impl LogEntry {
+	/// Parses a single header line.
+	///
+	/// Returns `None` if the line doesn't start with a header.
+	pub fn parse_line(line: &str) -> Option<Self> {
+		let line = line.strip_suffix('\n').unwrap_or(line);
+		let line = line.strip_suffix('\r').unwrap_or(line);
+
+		let rest = line.strip_prefix("[+")?;
+		let (timestamp, rest) = rest.split_once(']')?;
+		let (duration_since_start, timestamp_format) = parse_timestamp_with_format(timestamp)?;
+
+		let rest = rest.strip_prefix('[')?;
+		let (level, rest) = rest.split_once(']')?;
+		let level = Level::from_str(level).ok()?;
+
+		let mut fields = Vec::new();
+		let mut rest = rest;
+		while let Some(field) = rest.strip_prefix('[') {
+			let (key, field) = field.split_once('=')?;
+			if key.contains(']') {
+				return None
+			}
+
+			// values escape `\` and `]` with a preceding `\`
+			let mut value = String::new();
+			let mut chars = field.char_indices();
+			rest = loop {
+				match chars.next()? {
+					(index, ']') => break &field[index + 1 ..],
+					(_, '\\') => value.push(chars.next()?.1),
+					(_, c) => value.push(c)
+				}
+			};
+			fields.push((key.to_string(), value));
+		}
+
+		let rest = rest.strip_prefix(" (")?;
+		let (target, message) = match rest.split_once(") ") {
+			Some(split) => split,
+			None => (rest.strip_suffix(')')?, "")
+		};
+
+		Some(LogEntry {
+			duration_since_start,
+			timestamp_format,
+			level,
+			fields,
+			target: target.to_string(),
+			message: message.to_string()
+		})
+	}
+}
+impl Display for LogEntry {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		LogLine::from_parts(
+			Timestamp::from(self.duration_since_start).with_format(self.timestamp_format),
+			self.level,
+			&self.target,
+			&format_args!("{}", self.message)
+		)
+		.with_parsed_fields(&self.fields)
+		.fmt(f)
+	}
+}
+impl FromStr for LogEntry {
+	type Err = ParseError;
+
+	/// Parses one entry, the message may span multiple lines.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut lines = s.lines();
+
+		let first = lines.next().unwrap_or("");
+		let mut entry = LogEntry::parse_line(first).ok_or_else(|| ParseError::UnexpectedLine {
+			line_number: 1,
+			line: first.to_string()
+		})?;
+		for line in lines {
+			entry.message.push('\n');
+			entry.message.push_str(line);
+		}
+
+		Ok(entry)
+	}
+}
+
+/// Parses a timestamp (without the leading `+`) into a duration.
+///
+/// Accepts all layouts of [`TimestampFormat`](../targets/util/struct.TimestampFormat.html):
+/// `mmm:ss.f`, `hh:mm:ss.f` and `Dd hh:mm:ss.f`. The fraction has 3 (or 4 in the default layout) digits for milliseconds,
+/// 6 for microseconds and 9 for nanoseconds.
+pub fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+	parse_timestamp_with_format(timestamp).map(|(duration, _)| duration)
+}
+
+/// Parses a timestamp like [`parse_timestamp`](fn.parse_timestamp.html) and returns the format it is written in.
+///
+/// Delta timestamps look the same as timestamps since start, so `delta` is always `false`.
+pub fn parse_timestamp_with_format(timestamp: &str) -> Option<(Duration, TimestampFormat)> {
+	fn number(digits: &str) -> Option<u64> {
+		if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+			return None
+		}
+
+		digits.parse().ok()
+	}
+
+	let (days, rest) = match timestamp.split_once("d ") {
+		Some((days, rest)) => (Some(number(days)?), rest),
+		None => (None, timestamp)
+	};
+	let (rest, fraction) = rest.split_once('.')?;
+
+	let mut parts = rest.split(':').rev();
+	let seconds = number(parts.next()?)?;
+	let minutes = number(parts.next()?)?;
+	let hours = match parts.next() {
+		Some(hours) if minutes < 60 => Some(number(hours)?),
+		Some(_) => return None,
+		None => None
+	};
+	if parts.next().is_some()
+		|| seconds >= 60
+		|| (days.is_some() && matches!(hours, Some(hours) if hours >= 24))
+	{
+		return None
+	}
+	let secs = days.unwrap_or(0) * 86400 + hours.unwrap_or(0) * 3600 + minutes * 60 + seconds;
+
+	let (nanos, precision) = match fraction.len() {
+		3 | 4 => (number(fraction)? * 1_000_000, Precision::Millis),
+		6 => (number(fraction)? * 1_000, Precision::Micros),
+		9 => (number(fraction)?, Precision::Nanos),
+		_ => return None
+	};
+	if nanos >= 1_000_000_000 {
+		return None
+	}
+
+	let largest_unit = match (days, hours) {
+		(Some(_), _) => LargestUnit::Days,
+		(None, Some(_)) => LargestUnit::Hours,
+		(None, None) => LargestUnit::Minutes
+	};
+	let format = TimestampFormat::DEFAULT
+		.with_precision(precision)
+		.with_largest_unit(largest_unit);
+
+	Some((Duration::new(secs, nanos as u32), format))
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+	Io(io::Error),
+	/// A continuation line was found before any header line.
+	UnexpectedLine {
+		line_number: usize,
+		line: String
+	}
+}
+impl Display for ParseError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			ParseError::Io(err) => write!(f, "{}", err),
+			ParseError::UnexpectedLine { line_number, line } => {
+				write!(f, "line {} is not a log entry: {}", line_number, line)
+			}
+		}
+	}
+}
+impl std::error::Error for ParseError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			ParseError::Io(err) => Some(err),
+			ParseError::UnexpectedLine { .. } => None
+		}
+	}
+}
+impl From<io::Error> for ParseError {
+	fn from(err: io::Error) -> Self {
+		ParseError::Io(err)
+	}
+}
+
+/// Iterator over entries read from a `BufRead`.
+///
+/// An entry is only yielded once the next header line or the end of the input is read,
+/// so that all its continuation lines are included.
+pub struct LogReader<R: BufRead> {
+	reader: R,
+	line: String,
+	line_number: usize,
+	pending: Option<LogEntry>
+}
+impl<R: BufRead> LogReader<R> {
+	pub fn new(reader: R) -> Self {
+		LogReader {
+			reader,
+			line: String::new(),
+			line_number: 0,
+			pending: None
+		}
+	}
+
+	/// Returns the entry that is waiting for possible continuation lines, if any.
+	///
+	/// Can be used when following a file that is still being written to.
+	pub fn take_pending(&mut self) -> Option<LogEntry> {
+		self.pending.take()
+	}
+
+	/// Returns a mutable reference to the underlying `BufRead`.
+	pub fn get_mut(&mut self) -> &mut R {
+		&mut self.reader
+	}
+
+	/// Consumes the reader and returns the underlying `BufRead`.
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+}
+impl<R: BufRead> Iterator for LogReader<R> {
+	type Item = Result<LogEntry, ParseError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			self.line.clear();
+			match self.reader.read_line(&mut self.line) {
+				Err(err) => return Some(Err(err.into())),
+				Ok(0) => return self.pending.take().map(Ok),
+				Ok(_) => ()
+			}
+			self.line_number += 1;
+
+			if let Some(entry) = LogEntry::parse_line(&self.line) {
+				if let Some(previous) = self.pending.replace(entry) {
+					return Some(Ok(previous))
+				}
+				continue
+			}
+
+			let line = self.line.trim_end_matches(['\n', '\r']);
+			match self.pending {
+				Some(ref mut entry) => {
+					entry.message.push('\n');
+					entry.message.push_str(line);
+				}
+				None => {
+					return Some(Err(ParseError::UnexpectedLine {
+						line_number: self.line_number,
+						line: line.to_string()
+					}))
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use super::{parse_timestamp, LogEntry, LogReader, ParseError};
+
+	#[test]
+	fn display_keeps_timestamp_format() {
+		for line in [
+			"[+062:03.0004][INFO] (app) message",
+			"[+01:02:03.004005][INFO] (app) message",
+			"[+1d 01:02:03.004005006][INFO] (app) message",
+			"[+1d 01:02:03.004][INFO] (app) message"
+		] {
+			let entry: LogEntry = line.parse().unwrap();
+			assert_eq!(entry.to_string(), line);
+		}
+	}
+
+	#[test]
+	fn timestamp_layouts() {
+		assert_eq!(
+			parse_timestamp("062:03.0004"),
+			Some(Duration::new(3723, 4_000_000))
+		);
+		assert_eq!(
+			parse_timestamp("01:02:03.004005"),
+			Some(Duration::new(3723, 4_005_000))
+		);
+		assert_eq!(
+			parse_timestamp("1d 01:02:03.000000001"),
+			Some(Duration::new(90123, 1))
+		);
+	}
+
+	#[test]
+	fn timestamp_rejections() {
+		for timestamp in [
+			// fraction lengths other than 3, 4, 6 and 9
+			"000:01.00",
+			"000:01.00000",
+			"000:01.0000000000",
+			"000:01",
+			// seconds and minutes out of range
+			"000:60.0000",
+			"01:60:00.000",
+			// hours out of range with days
+			"1d 24:00:00.000",
+			"000:0x.0000",
+			"1:2:3:4.000"
+		] {
+			assert_eq!(parse_timestamp(timestamp), None, "{}", timestamp);
+		}
+	}
+
+	#[test]
+	fn reader_reports_unexpected_line() {
+		let input = "orphan\n[+000:01.0000][INFO] (app) message\n";
+		let mut reader = LogReader::new(input.as_bytes());
+
+		match reader.next() {
+			Some(Err(ParseError::UnexpectedLine { line_number, line })) => {
+				assert_eq!((line_number, line.as_str()), (1, "orphan"))
+			}
+			other => panic!("expected an unexpected line, got {:?}", other)
+		}
+		assert_eq!(reader.next().unwrap().unwrap().message, "message");
+		assert!(reader.next().is_none());
+	}
+
+	#[test]
+	fn reader_accepts_crlf() {
+		let input =
+			"[+000:01.0000][INFO] (app) first\r\n  second\r\n[+000:02.0000][WARN] (app) third\r\n";
+		let entries: Vec<LogEntry> = LogReader::new(input.as_bytes())
+			.collect::<Result<_, _>>()
+			.unwrap();
+
+		assert_eq!(entries[0].message, "first\n  second");
+		assert_eq!(entries[1].message, "third");
+		assert_eq!(entries.len(), 2);
+	}
+
+	#[test]
+	fn field_values_are_escaped() {
+		let line = r"[+000:00.0000][INFO][thread=a\]b\\][pid=7] (app) message";
+		let entry: LogEntry = line.parse().unwrap();
+
+		assert_eq!(
+			entry.fields,
+			[
+				("thread".to_string(), r"a]b\".to_string()),
+				("pid".to_string(), "7".to_string())
+			]
+		);
+		assert_eq!(entry.to_string(), line);
+	}
+}
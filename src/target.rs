@@ -1,6 +1,9 @@
 use std::time::Duration;
 
-use log::{Level, LevelFilter, Record};
+use log::{Level, LevelFilter, Metadata, Record};
+
+#[cfg(feature = "std")]
+use crate::targets::util::layout::{Layout, RenderCache};
 
 /// Represents a log target.
 pub trait Target {
@@ -13,9 +16,17 @@ pub trait Target {
 	/// Returns the max level this target logs.
 	fn level(&self) -> Level;
 
+	/// Whether the target would write a record with given metadata.
+	///
+	/// Used to implement `Log::enabled`, so that expensive records are not even built when no target wants them.
+	/// The default implementation compares the level against [`level`](#tymethod.level).
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		metadata.level() <= self.level()
+	}
+
 	/// Whether the target wants to ignore given record.
 	///
-	/// This method is called before `write` to filter output.
+	/// This method is called before `write`, after `enabled`, to filter output based on the whole record.
 	fn ignore(&self, _record: &Record) -> bool {
 		false
 	}
@@ -23,8 +34,40 @@ pub trait Target {
 	/// Writes record to the target output.
 	fn write(&self, duration_since_start: Duration, record: &Record) -> Result<(), Self::Error>;
 
+	/// Returns the layout the target would render a record logged at `duration_since_start` in.
+	///
+	/// When targets are written to through [`Targets`](trait.Targets.html), a record is rendered only once for all
+	/// targets with equal layouts and passed to [`write_rendered`](#method.write_rendered) instead of `write`.
+	/// The default returns `None`, meaning the target renders records by itself.
+	#[cfg(feature = "std")]
+	fn layout(&self, _duration_since_start: Duration) -> Option<Layout<'_>> {
+		None
+	}
+
+	/// Writes `record` rendered according to [`layout`](#method.layout) as `line`, including the trailing newline.
+	///
+	/// The default ignores `line` and calls [`write`](#tymethod.write), targets returning a layout should
+	/// implement it to avoid rendering the record again.
+	#[cfg(feature = "std")]
+	fn write_rendered(
+		&self,
+		duration_since_start: Duration,
+		record: &Record,
+		_line: &[u8]
+	) -> Result<(), Self::Error> {
+		self.write(duration_since_start, record)
+	}
+
 	/// Flushes target output.
 	fn flush(&self) -> Result<(), Self::Error>;
+
+	/// Flushes target output and stops any background work of the target.
+	///
+	/// Called when a [`LoggerGuard`](../struct.LoggerGuard.html) is dropped. The target must keep accepting
+	/// records afterwards, since the logger stays installed.
+	fn shutdown(&self) -> Result<(), Self::Error> {
+		self.flush()
+	}
 }
 
 pub trait Targets {
@@ -33,11 +76,17 @@ pub trait Targets {
 	/// Returns the max level any of the target logs to.
 	fn max_level(&self) -> LevelFilter;
 
+	/// Whether any of the targets would write a record with given metadata.
+	fn enabled(&self, metadata: &Metadata) -> bool;
+
 	/// Writes record to the target outputs.
 	fn write(&self, duration_since_start: Duration, record: &Record) -> Self::Results;
 
 	/// Flushes target outputs.
 	fn flush(&self) -> Self::Results;
+
+	/// Shuts down target outputs.
+	fn shutdown(&self) -> Self::Results;
 }
 impl<T: Target> Targets for T {
 	type Results = Result<(), T::Error>;
@@ -46,17 +95,61 @@ impl<T: Target> Targets for T {
 		Target::level(self).to_level_filter()
 	}
 
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		Target::enabled(self, metadata)
+	}
+
 	fn write(&self, duration_since_start: Duration, record: &Record) -> Self::Results {
-		if !Target::ignore(self, record) {
-			Target::write(self, duration_since_start, record)
-		} else {
-			Ok(())
-		}
+		#[cfg(feature = "std")]
+		return RenderCache::with(|cache| write_target(self, cache, duration_since_start, record));
+
+		#[cfg(not(feature = "std"))]
+		write_target(self, duration_since_start, record)
 	}
 
 	fn flush(&self) -> Self::Results {
 		Target::flush(self)
 	}
+
+	fn shutdown(&self) -> Self::Results {
+		Target::shutdown(self)
+	}
+}
+
+/// Writes record to the target if it is enabled and not ignored, sharing the rendered line through `cache`.
+///
+/// If the line can't be rendered, the target is left to render and report the error itself.
+#[cfg(feature = "std")]
+fn write_target<'a, T: Target>(
+	target: &'a T,
+	cache: &mut RenderCache<'a, '_>,
+	duration_since_start: Duration,
+	record: &Record
+) -> Result<(), T::Error> {
+	if !target.enabled(record.metadata()) || target.ignore(record) {
+		return Ok(())
+	}
+
+	match target.layout(duration_since_start) {
+		Some(layout) => match cache.line(layout, record) {
+			Ok(line) => target.write_rendered(duration_since_start, record, line),
+			Err(_) => target.write(duration_since_start, record)
+		},
+		None => target.write(duration_since_start, record)
+	}
+}
+
+#[cfg(not(feature = "std"))]
+fn write_target<T: Target>(
+	target: &T,
+	duration_since_start: Duration,
+	record: &Record
+) -> Result<(), T::Error> {
+	if target.enabled(record.metadata()) && !target.ignore(record) {
+		target.write(duration_since_start, record)
+	} else {
+		Ok(())
+	}
 }
 
 pub trait TargetResults {
@@ -97,18 +190,28 @@ macro_rules! impl_for_tuple {
 				max
 			}
 
+			fn enabled(&self, metadata: &Metadata) -> bool {
+				$(
+					self.$gen_num.enabled(metadata)
+				)||+
+			}
+
 			fn write(
 				&self,
 				duration_since_start: Duration,
 				record: &Record
 			) -> Self::Results {
+				#[cfg(feature = "std")]
+				return RenderCache::with(|cache| (
+					$(
+						write_target(&self.$gen_num, cache, duration_since_start, record),
+					)+
+				));
+
+				#[cfg(not(feature = "std"))]
 				(
 					$(
-						if !self.$gen_num.ignore(record) {
-							self.$gen_num.write(duration_since_start, record)
-						} else {
-							Ok(())
-						},
+						write_target(&self.$gen_num, duration_since_start, record),
 					)+
 				)
 			}
@@ -120,6 +223,14 @@ macro_rules! impl_for_tuple {
 					)+
 				)
 			}
+
+			fn shutdown(&self) -> Self::Results {
+				(
+					$(
+						self.$gen_num.shutdown(),
+					)+
+				)
+			}
 		}
 
 		impl<$($gen_name: core::fmt::Display),+> TargetResults for ($(Result<(), $gen_name>,)+) {
@@ -143,3 +254,98 @@ impl_for_tuple!(A:0, B:1, C:2, D:3, E:4);
 impl_for_tuple!(A:0, B:1, C:2, D:3, E:4, F:5);
 impl_for_tuple!(A:0, B:1, C:2, D:3, E:4, F:5, G:6);
 impl_for_tuple!(A:0, B:1, C:2, D:3, E:4, F:5, G:6, H:7);
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::{cell::Cell, io, time::Duration};
+
+	use log::{Level, Record};
+
+	use super::{Target, Targets};
+	use crate::targets::util::layout::Layout;
+
+	/// Target counting the records written to it.
+	struct Counting {
+		level: Level,
+		count: Cell<usize>
+	}
+	impl Counting {
+		fn new(level: Level) -> Self {
+			Counting {
+				level,
+				count: Cell::new(0)
+			}
+		}
+	}
+	impl Target for Counting {
+		type Error = io::Error;
+
+		fn level(&self) -> Level {
+			self.level
+		}
+
+		fn write(&self, _duration_since_start: Duration, _record: &Record) -> io::Result<()> {
+			self.count.set(self.count.get() + 1);
+			Ok(())
+		}
+
+		fn flush(&self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	/// Target with a layout that doesn't implement `write_rendered`.
+	struct LayoutOnly(Cell<usize>);
+	impl Target for LayoutOnly {
+		type Error = io::Error;
+
+		fn level(&self) -> Level {
+			Level::Trace
+		}
+
+		fn write(&self, _duration_since_start: Duration, _record: &Record) -> io::Result<()> {
+			self.0.set(self.0.get() + 1);
+			Ok(())
+		}
+
+		fn layout(&self, duration_since_start: Duration) -> Option<Layout<'_>> {
+			Some(Layout::plain(duration_since_start))
+		}
+
+		fn flush(&self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn write_rendered_defaults_to_write() {
+		let targets = (LayoutOnly(Cell::new(0)), LayoutOnly(Cell::new(0)));
+		let record = Record::builder()
+			.level(Level::Info)
+			.args(format_args!("shared"))
+			.build();
+
+		let (first, second) = Targets::write(&targets, Duration::ZERO, &record);
+		assert!(first.is_ok() && second.is_ok());
+		assert_eq!((targets.0 .0.get(), targets.1 .0.get()), (1, 1));
+	}
+
+	#[test]
+	fn tuple_filters_by_each_level() {
+		let targets = (Counting::new(Level::Warn), Counting::new(Level::Trace));
+		let record = Record::builder()
+			.level(Level::Debug)
+			.args(format_args!("debug"))
+			.build();
+
+		assert!(Targets::enabled(&targets, record.metadata()));
+		assert!(!Targets::enabled(
+			&(Counting::new(Level::Warn), Counting::new(Level::Info)),
+			record.metadata()
+		));
+
+		let (first, second) = Targets::write(&targets, Duration::ZERO, &record);
+		assert!(first.is_ok() && second.is_ok());
+		assert_eq!((targets.0.count.get(), targets.1.count.get()), (0, 1));
+	}
+}
@@ -0,0 +1,419 @@
+//! Target combinator switching to a secondary target when the primary one fails.
+
+use std::{
+	fmt::{self, Display, Formatter},
+	sync::atomic::{AtomicBool, AtomicU32, Ordering},
+	time::Duration
+};
+
+use log::{Level, Metadata, Record};
+
+use crate::target::Target;
+
+/// Target of the notice records written when switching between the targets of a `Fallback`.
+pub const NOTICE_TARGET: &str = "edwardium_logger::fallback";
+
+#[derive(Debug)]
+pub enum FallbackError<P, S> {
+	Primary(P),
+	Secondary(S)
+}
+impl<P: Display, S: Display> Display for FallbackError<P, S> {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			FallbackError::Primary(err) => write!(f, "primary target: {}", err),
+			FallbackError::Secondary(err) => write!(f, "secondary target: {}", err)
+		}
+	}
+}
+#[cfg(feature = "std")]
+impl<P: std::error::Error, S: std::error::Error> std::error::Error for FallbackError<P, S> {}
+
+/// Writes records to `primary` and switches to `secondary` once writing to `primary` fails,
+/// for example because the disk is full.
+///
+/// While switched over, every `probe_interval` one record that `primary` would write is written to it again to check
+/// whether it recovered, and on success the target switches back. All other records go to `secondary`. A single `Warn` notice record with target
+/// [`NOTICE_TARGET`](constant.NOTICE_TARGET.html) is written on each switch, to the target that is switched to.
+///
+/// ```no_run
+/// use std::path::Path;
+///
+/// use edwardium_logger::targets::{fallback::Fallback, file::FileTarget, stderr::StderrTarget};
+///
+/// let target = Fallback::new(
+/// 	FileTarget::new(log::Level::Info, Path::new("app.log"), Default::default())?,
+/// 	StderrTarget::new(log::Level::Info, Default::default())
+/// );
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Fallback<P: Target, S: Target> {
+	primary: P,
+	secondary: S,
+	probe_interval: Duration,
+	failed_over: AtomicBool,
+	/// Duration since start, in milliseconds wrapping around every ~49 days, after which the primary target is probed.
+	next_probe: AtomicU32
+}
+impl<P: Target, S: Target> Fallback<P, S> {
+	pub const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+	const MAX_PROBE_INTERVAL: Duration = Duration::from_secs(24 * 86400);
+
+	pub const fn new(primary: P, secondary: S) -> Self {
+		Fallback {
+			primary,
+			secondary,
+			probe_interval: Self::DEFAULT_PROBE_INTERVAL,
+			failed_over: AtomicBool::new(false),
+			next_probe: AtomicU32::new(0)
+		}
+	}
+
+	/// Sets how often the primary target is probed while switched over, `DEFAULT_PROBE_INTERVAL` by default.
+	///
+	/// Intervals are capped at 24 days.
+	pub const fn with_probe_interval(mut self, probe_interval: Duration) -> Self {
+		self.probe_interval = probe_interval;
+		self
+	}
+
+	pub fn primary(&self) -> &P {
+		&self.primary
+	}
+
+	pub fn secondary(&self) -> &S {
+		&self.secondary
+	}
+
+	/// Whether the records currently go to the secondary target.
+	pub fn is_failed_over(&self) -> bool {
+		self.failed_over.load(Ordering::Acquire)
+	}
+
+	/// Truncates `duration` to wrapping milliseconds.
+	fn millis(duration: Duration) -> u32 {
+		duration.as_millis() as u32
+	}
+
+	fn next_probe_after(&self, duration_since_start: Duration) -> u32 {
+		let interval = Self::millis(self.probe_interval.min(Self::MAX_PROBE_INTERVAL));
+		Self::millis(duration_since_start).wrapping_add(interval)
+	}
+
+	/// Whether the probe time `next_probe` was reached, correct as long as it is less than 24 days away.
+	fn is_probe_due(next_probe: u32, duration_since_start: Duration) -> bool {
+		Self::millis(duration_since_start).wrapping_sub(next_probe) as i32 >= 0
+	}
+
+	fn write_to<T: Target>(
+		target: &T,
+		duration_since_start: Duration,
+		record: &Record
+	) -> Result<(), T::Error> {
+		if target.enabled(record.metadata()) && !target.ignore(record) {
+			target.write(duration_since_start, record)
+		} else {
+			Ok(())
+		}
+	}
+
+	fn write_secondary(
+		&self,
+		duration_since_start: Duration,
+		record: &Record
+	) -> Result<(), FallbackError<P::Error, S::Error>> {
+		Self::write_to(&self.secondary, duration_since_start, record)
+			.map_err(FallbackError::Secondary)
+	}
+}
+impl<P: Target, S: Target> Target for Fallback<P, S> {
+	type Error = FallbackError<P::Error, S::Error>;
+
+	/// Includes the level of the secondary target, so that the maximum level set when the logger is initialized
+	/// lets its records through after switching over.
+	fn level(&self) -> Level {
+		self.primary.level().max(self.secondary.level())
+	}
+
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		match self.failed_over.load(Ordering::Acquire) {
+			true => self.primary.enabled(metadata) || self.secondary.enabled(metadata),
+			false => self.primary.enabled(metadata)
+		}
+	}
+
+	fn write(&self, duration_since_start: Duration, record: &Record) -> Result<(), Self::Error> {
+		if self.failed_over.load(Ordering::Acquire) {
+			// only records the primary target would write can probe it, and only the thread that moves
+			// the probe time forward probes
+			let next_probe = self.next_probe.load(Ordering::Relaxed);
+			let probe = self.primary.enabled(record.metadata())
+				&& !self.primary.ignore(record)
+				&& Self::is_probe_due(next_probe, duration_since_start)
+				&& self
+					.next_probe
+					.compare_exchange(
+						next_probe,
+						self.next_probe_after(duration_since_start),
+						Ordering::Relaxed,
+						Ordering::Relaxed
+					)
+					.is_ok();
+			if !probe {
+				return self.write_secondary(duration_since_start, record)
+			}
+
+			if self.primary.write(duration_since_start, record).is_err() {
+				return self.write_secondary(duration_since_start, record)
+			}
+
+			if self
+				.failed_over
+				.compare_exchange(true, false, Ordering::AcqRel, Ordering::Acquire)
+				.is_ok()
+			{
+				let _ = Self::write_to(
+					&self.primary,
+					duration_since_start,
+					&Record::builder()
+						.level(Level::Warn)
+						.target(NOTICE_TARGET)
+						.args(format_args!(
+							"primary target recovered, switching back from the secondary target"
+						))
+						.build()
+				);
+			}
+
+			return Ok(())
+		}
+
+		match Self::write_to(&self.primary, duration_since_start, record) {
+			Ok(()) => Ok(()),
+			Err(err) => {
+				self.next_probe.store(
+					self.next_probe_after(duration_since_start),
+					Ordering::Relaxed
+				);
+				let notice = match self.failed_over.swap(true, Ordering::AcqRel) {
+					true => Ok(()),
+					false => self.write_secondary(
+						duration_since_start,
+						&Record::builder()
+							.level(Level::Warn)
+							.target(NOTICE_TARGET)
+							.args(format_args!(
+								"primary target failed, switching to the secondary target: {}",
+								err
+							))
+							.build()
+					)
+				};
+
+				// the record is written even if the notice could not be
+				self.write_secondary(duration_since_start, record)
+					.and(notice)
+			}
+		}
+	}
+
+	fn flush(&self) -> Result<(), Self::Error> {
+		let primary = self.primary.flush();
+		self.secondary.flush().map_err(FallbackError::Secondary)?;
+
+		// the primary target is expected to fail while switched over
+		match self.failed_over.load(Ordering::Acquire) {
+			true => Ok(()),
+			false => primary.map_err(FallbackError::Primary)
+		}
+	}
+
+	fn shutdown(&self) -> Result<(), Self::Error> {
+		let primary = self.primary.shutdown();
+		self.secondary
+			.shutdown()
+			.map_err(FallbackError::Secondary)?;
+
+		match self.failed_over.load(Ordering::Acquire) {
+			true => Ok(()),
+			false => primary.map_err(FallbackError::Primary)
+		}
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::{
+		io,
+		sync::{
+			atomic::{AtomicBool, Ordering},
+			Mutex
+		},
+		time::Duration
+	};
+
+	use log::{Level, Metadata, Record};
+
+	use super::{Fallback, NOTICE_TARGET};
+	use crate::target::Target;
+
+	struct Mock {
+		level: Level,
+		fail: AtomicBool,
+		fail_notices: AtomicBool,
+		lines: Mutex<Vec<String>>
+	}
+	impl Mock {
+		fn new(level: Level) -> Self {
+			Mock {
+				level,
+				fail: AtomicBool::new(false),
+				fail_notices: AtomicBool::new(false),
+				lines: Mutex::new(Vec::new())
+			}
+		}
+
+		fn take(&self) -> Vec<String> {
+			std::mem::take(&mut *self.lines.lock().unwrap())
+		}
+	}
+	impl Target for Mock {
+		type Error = io::Error;
+
+		fn level(&self) -> Level {
+			self.level
+		}
+
+		fn write(&self, _duration_since_start: Duration, record: &Record) -> io::Result<()> {
+			if self.fail.load(Ordering::Relaxed) {
+				return Err(io::Error::other("failed"))
+			}
+
+			let line = match record.target() {
+				NOTICE_TARGET if self.fail_notices.load(Ordering::Relaxed) => {
+					return Err(io::Error::other("failed"))
+				}
+				NOTICE_TARGET => "notice".to_string(),
+				_ => record.args().to_string()
+			};
+			self.lines.lock().unwrap().push(line);
+			Ok(())
+		}
+
+		fn flush(&self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	fn write(target: &Fallback<Mock, Mock>, secs: u64, level: Level, message: &str) {
+		target
+			.write(
+				Duration::from_secs(secs),
+				&Record::builder()
+					.level(level)
+					.args(format_args!("{}", message))
+					.build()
+			)
+			.unwrap();
+	}
+
+	fn fallback() -> Fallback<Mock, Mock> {
+		Fallback::new(Mock::new(Level::Info), Mock::new(Level::Trace))
+			.with_probe_interval(Duration::from_secs(10))
+	}
+
+	#[test]
+	fn switches_over_and_back() {
+		let target = fallback();
+		write(&target, 0, Level::Info, "a");
+		assert!(!target.is_failed_over());
+
+		target.primary().fail.store(true, Ordering::Relaxed);
+		write(&target, 1, Level::Info, "b");
+		write(&target, 2, Level::Info, "c");
+		assert!(target.is_failed_over());
+		assert_eq!(target.primary().take(), ["a"]);
+		assert_eq!(target.secondary().take(), ["notice", "b", "c"]);
+
+		// probe while the primary target still fails
+		write(&target, 11, Level::Info, "d");
+		assert!(target.is_failed_over());
+		assert_eq!(target.secondary().take(), ["d"]);
+
+		target.primary().fail.store(false, Ordering::Relaxed);
+		// not yet time for the next probe
+		write(&target, 12, Level::Info, "e");
+		assert!(target.is_failed_over());
+		assert_eq!(target.secondary().take(), ["e"]);
+
+		write(&target, 21, Level::Info, "f");
+		write(&target, 22, Level::Info, "g");
+		assert!(!target.is_failed_over());
+		assert_eq!(target.primary().take(), ["f", "notice", "g"]);
+		assert!(target.secondary().take().is_empty());
+	}
+
+	#[test]
+	fn filtered_records_do_not_probe() {
+		let target = fallback();
+		target.primary().fail.store(true, Ordering::Relaxed);
+		write(&target, 0, Level::Info, "a");
+		target.primary().fail.store(false, Ordering::Relaxed);
+
+		// the primary target would not write the debug record, so it can't prove recovery
+		write(&target, 11, Level::Debug, "b");
+		assert!(target.is_failed_over());
+		assert!(target.primary().take().is_empty());
+		assert_eq!(target.secondary().take(), ["notice", "a", "b"]);
+
+		write(&target, 12, Level::Info, "c");
+		assert!(!target.is_failed_over());
+		assert_eq!(target.primary().take(), ["c", "notice"]);
+	}
+
+	#[test]
+	fn enabled_includes_secondary_only_when_failed_over() {
+		let target = fallback();
+		let debug = Metadata::builder().level(Level::Debug).build();
+		assert!(!target.enabled(&debug));
+
+		target.primary().fail.store(true, Ordering::Relaxed);
+		write(&target, 0, Level::Info, "a");
+		assert!(target.enabled(&debug));
+	}
+
+	#[test]
+	fn record_is_written_when_notice_fails() {
+		let target = fallback();
+		target.primary().fail.store(true, Ordering::Relaxed);
+		target
+			.secondary()
+			.fail_notices
+			.store(true, Ordering::Relaxed);
+
+		let result = target.write(
+			Duration::ZERO,
+			&Record::builder()
+				.level(Level::Info)
+				.args(format_args!("a"))
+				.build()
+		);
+		assert!(result.is_err());
+		assert_eq!(target.secondary().take(), ["a"]);
+	}
+
+	#[test]
+	fn probes_across_millisecond_wraparound() {
+		let target = fallback();
+		let wrap = u32::MAX as u64 / 1000;
+		target.primary().fail.store(true, Ordering::Relaxed);
+		write(&target, wrap - 5, Level::Info, "a");
+		target.primary().fail.store(false, Ordering::Relaxed);
+
+		write(&target, wrap + 1, Level::Info, "b");
+		assert!(target.is_failed_over());
+		write(&target, wrap + 6, Level::Info, "c");
+		assert!(!target.is_failed_over());
+		assert_eq!(target.primary().take(), ["c", "notice"]);
+	}
+}
@@ -1,22 +1,578 @@
 use std::{
-	fs::{File, OpenOptions},
+	fs::{self, File, OpenOptions},
 	io,
-	io::Write,
-	path::Path,
-	sync::Mutex,
-	time::Duration
+	io::{BufWriter, Write},
+	path::{Path, PathBuf},
+	sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+	thread::{self, JoinHandle},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH}
 };
 
-use log::{Level, Record};
+#[cfg(all(unix, feature = "sighup_reopen"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::{Level, Metadata, Record};
 
 use crate::target::Target;
 
-use super::util::ignore_list::{IgnoreList, IgnoreListPatterns};
+use super::util::{
+	ignore_list::{IgnoreList, IgnoreListPatterns},
+	layout::{self, Layout, LayoutStyle},
+	pattern::{civil_from_days, Pattern},
+	LineFields,
+	TimestampFormat,
+	TimestampFormatter
+};
+
+/// How a file is synced to the disk after `Error` records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+	None,
+	/// `fdatasync`, only the data and the metadata needed to read it back.
+	Data,
+	/// `fsync`, the data and all metadata.
+	All
+}
+
+/// When a `FileTarget` writes the buffered lines to its file.
+///
+/// Lines are buffered up to `buffer_size` bytes, the buffer is written once the next line doesn't fit.
+/// Larger buffers mean fewer syscalls, but the buffered lines are lost if the process crashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlushPolicy {
+	pub buffer_size: usize,
+	/// Write the buffer at least this often.
+	pub interval: Option<Duration>,
+	/// Write the buffer right after records at or above this severity.
+	pub flush_level: Option<Level>,
+	/// Sync the file after `Error` records, implies writing the buffer.
+	pub sync_on_error: SyncMode
+}
+impl FlushPolicy {
+	/// Every line is written to the file immediately, the default.
+	pub const UNBUFFERED: FlushPolicy = FlushPolicy {
+		buffer_size: 0,
+		interval: None,
+		flush_level: None,
+		sync_on_error: SyncMode::None
+	};
+
+	pub const fn buffered(buffer_size: usize) -> Self {
+		FlushPolicy {
+			buffer_size,
+			..FlushPolicy::UNBUFFERED
+		}
+	}
+
+	pub const fn with_interval(mut self, interval: Duration) -> Self {
+		self.interval = Some(interval);
+		self
+	}
+
+	pub const fn with_flush_level(mut self, level: Level) -> Self {
+		self.flush_level = Some(level);
+		self
+	}
+
+	pub const fn with_sync_on_error(mut self, sync: SyncMode) -> Self {
+		self.sync_on_error = sync;
+		self
+	}
+}
+impl Default for FlushPolicy {
+	fn default() -> Self {
+		FlushPolicy::UNBUFFERED
+	}
+}
+
+/// How the file of a `FileTarget` is opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileOptions {
+	/// Truncate the file when the target is created instead of appending to it.
+	///
+	/// Files reopened after a rotation are always appended to.
+	pub truncate: bool,
+	/// Create missing parent directories.
+	pub create_dirs: bool,
+	/// Permission bits of newly created files, before the umask is applied. Only used on Unix.
+	pub mode: Option<u32>,
+	/// Hold an exclusive advisory lock (`flock`) on the file, so that opening it fails
+	/// while another target or process holds it. Only supported on Unix, opening the file fails elsewhere.
+	pub lock: bool
+}
+impl FileOptions {
+	pub const DEFAULT: FileOptions = FileOptions {
+		truncate: false,
+		create_dirs: false,
+		mode: None,
+		lock: false
+	};
+
+	pub const fn with_truncate(mut self, truncate: bool) -> Self {
+		self.truncate = truncate;
+		self
+	}
+
+	pub const fn with_create_dirs(mut self, create_dirs: bool) -> Self {
+		self.create_dirs = create_dirs;
+		self
+	}
+
+	pub const fn with_mode(mut self, mode: u32) -> Self {
+		self.mode = Some(mode);
+		self
+	}
+
+	pub const fn with_lock(mut self, lock: bool) -> Self {
+		self.lock = lock;
+		self
+	}
+
+	fn open(&self, path: &Path, truncate: bool) -> io::Result<File> {
+		if self.create_dirs {
+			if let Some(parent) = path.parent() {
+				fs::create_dir_all(parent)?;
+			}
+		}
+
+		let mut options = OpenOptions::new();
+		options.append(true).create(true);
+		#[cfg(unix)]
+		if let Some(mode) = self.mode {
+			use std::os::unix::fs::OpenOptionsExt;
+
+			options.mode(mode);
+		}
+		let file = options.open(path)?;
+
+		if self.lock {
+			lock_file(&file, path)?;
+		}
+		// only truncate once the lock is held, so that the file of another logger is left alone
+		if truncate {
+			file.set_len(0)?;
+		}
+
+		Ok(file)
+	}
+}
+impl Default for FileOptions {
+	fn default() -> Self {
+		FileOptions::DEFAULT
+	}
+}
+
+/// Takes an exclusive advisory lock on `file` without blocking.
+#[cfg(unix)]
+fn lock_file(file: &File, path: &Path) -> io::Result<()> {
+	use std::os::unix::io::AsRawFd;
+
+	// SAFETY: the descriptor is owned by `file` and stays open for the duration of the call
+	if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+		let err = io::Error::last_os_error();
+		if err.kind() == io::ErrorKind::WouldBlock {
+			return Err(io::Error::new(
+				io::ErrorKind::WouldBlock,
+				format!("{} is locked by another logger", path.display())
+			))
+		}
+		return Err(err)
+	}
+
+	Ok(())
+}
+#[cfg(not(unix))]
+fn lock_file(_file: &File, path: &Path) -> io::Result<()> {
+	Err(io::Error::new(
+		io::ErrorKind::Unsupported,
+		format!(
+			"cannot lock {}, file locks are only supported on Unix",
+			path.display()
+		)
+	))
+}
+
+/// Expands placeholders in a file path template.
+///
+/// | Placeholder | Value |
+/// |-------------|-------|
+/// | `{pid}` | process id |
+/// | `{date}` | current UTC date, `YYYY-MM-DD` |
+/// | `{hostname}` | host name |
+/// | `{exe}` | file name of the current executable without extension |
+///
+/// `{{` and `}}` are literal braces. The template is expanded once, when the target is created.
+///
+/// ```
+/// use edwardium_logger::targets::file::expand_path_template;
+///
+/// let path = expand_path_template("/var/log/app/{exe}.{pid}.log").unwrap();
+/// assert_eq!(
+/// 	path.to_str().unwrap(),
+/// 	format!(
+/// 		"/var/log/app/{}.{}.log",
+/// 		std::env::current_exe()
+/// 			.unwrap()
+/// 			.file_stem()
+/// 			.unwrap()
+/// 			.to_str()
+/// 			.unwrap(),
+/// 		std::process::id()
+/// 	)
+/// );
+/// ```
+pub fn expand_path_template(template: &str) -> io::Result<PathBuf> {
+	expand_template(template, false).map(PathBuf::from)
+}
+
+/// Expands `template`, with `glob` `{date}` becomes `*` to match the files of earlier days.
+///
+/// `{pid}` is expanded even then, the files of other processes may still be open.
+fn expand_template(template: &str, glob: bool) -> io::Result<String> {
+	let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+	let mut path = String::new();
+	let mut rest = template;
+	while let Some(index) = rest.find(['{', '}']) {
+		path.push_str(&rest[.. index]);
+		let brace = &rest[index .. index + 1];
+		rest = &rest[index + 1 ..];
+
+		if let Some(after) = rest.strip_prefix(brace) {
+			path.push_str(brace);
+			rest = after;
+			continue
+		}
+		if brace == "}" {
+			return Err(invalid(format!("unmatched }} in {}", template)))
+		}
+
+		let (placeholder, after) = rest
+			.split_once('}')
+			.ok_or_else(|| invalid(format!("unclosed placeholder in {}", template)))?;
+		rest = after;
+
+		match placeholder {
+			"date" if glob => path.push('*'),
+			"pid" => path.push_str(&std::process::id().to_string()),
+			"date" => {
+				let since_epoch = SystemTime::now()
+					.duration_since(UNIX_EPOCH)
+					.unwrap_or(Duration::ZERO);
+				let (year, month, day) = civil_from_days((since_epoch.as_secs() / 86400) as i64);
+				path.push_str(&format!("{:04}-{:02}-{:02}", year, month, day));
+			}
+			"hostname" => path.push_str(&hostname()?),
+			"exe" => {
+				let exe = std::env::current_exe()?;
+				let name = exe.file_stem().ok_or_else(|| {
+					invalid(format!("executable {} has no file name", exe.display()))
+				})?;
+				path.push_str(&name.to_string_lossy());
+			}
+			placeholder => {
+				return Err(invalid(format!(
+					"unknown placeholder {{{}}} in {}",
+					placeholder, template
+				)))
+			}
+		}
+	}
+	path.push_str(rest);
+
+	Ok(path)
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any characters and `?` one character.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let name: Vec<char> = name.chars().collect();
+
+	// position after the last `*` and the name position it was matched up to
+	let mut backtrack = None;
+	let (mut p, mut n) = (0, 0);
+	while n < name.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				p += 1;
+				backtrack = Some((p, n));
+			}
+			Some(&c) if c == '?' || c == name[n] => {
+				p += 1;
+				n += 1;
+			}
+			_ => match backtrack {
+				Some((star_p, star_n)) => {
+					p = star_p;
+					n = star_n + 1;
+					backtrack = Some((star_p, star_n + 1));
+				}
+				None => return false
+			}
+		}
+	}
+
+	pattern[p ..].iter().all(|&c| c == '*')
+}
+
+#[cfg(unix)]
+fn hostname() -> io::Result<String> {
+	let mut buffer = [0u8; 256];
+	// SAFETY: the buffer is valid for writes of its length
+	if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+		return Err(io::Error::last_os_error())
+	}
+
+	let length = buffer
+		.iter()
+		.position(|&byte| byte == 0)
+		.unwrap_or(buffer.len());
+	Ok(String::from_utf8_lossy(&buffer[.. length]).into_owned())
+}
+#[cfg(not(unix))]
+fn hostname() -> io::Result<String> {
+	std::env::var("COMPUTERNAME")
+		.map_err(|_| io::Error::new(io::ErrorKind::NotFound, "COMPUTERNAME is not set"))
+}
+
+struct FileOutput {
+	writer: BufWriter<File>,
+	path: PathBuf,
+	options: FileOptions,
+	file_id: Option<(u64, u64)>,
+	policy: FlushPolicy,
+	/// Set on shutdown, when there is no flusher thread anymore.
+	flush_each: bool,
+	reopen_check_interval: Option<Duration>,
+	last_reopen_check: Instant
+}
+impl FileOutput {
+	fn reopen(&mut self) -> io::Result<()> {
+		// lines buffered before the file was rotated belong to the old file
+		self.writer.flush()?;
+
+		let file = self.options.open(&self.path, false)?;
+		self.file_id = file_id(&file.metadata()?);
+		self.writer = BufWriter::with_capacity(self.writer.capacity(), file);
+
+		Ok(())
+	}
+
+	/// Reopens the file if the path now points to a different file or to none.
+	fn check_reopen(&mut self) -> io::Result<()> {
+		match self.reopen_check_interval {
+			Some(interval) if self.last_reopen_check.elapsed() >= interval => (),
+			_ => return Ok(())
+		}
+		self.last_reopen_check = Instant::now();
+
+		match fs::metadata(&self.path) {
+			Ok(metadata) if file_id(&metadata) == self.file_id => Ok(()),
+			Ok(_) => self.reopen(),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => self.reopen(),
+			Err(err) => Err(err)
+		}
+	}
+
+	fn write(&mut self, level: Level, line: &[u8]) -> io::Result<()> {
+		self.check_reopen()?;
+		self.writer.write_all(line)?;
+
+		if level == Level::Error && self.policy.sync_on_error != SyncMode::None {
+			self.writer.flush()?;
+			match self.policy.sync_on_error {
+				SyncMode::None => (),
+				SyncMode::Data => self.writer.get_ref().sync_data()?,
+				SyncMode::All => self.writer.get_ref().sync_all()?
+			}
+		} else if self.flush_each
+			|| self
+				.policy
+				.flush_level
+				.is_some_and(|flush_level| level <= flush_level)
+		{
+			self.writer.flush()?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Which files around the file of a `FileTarget` are deleted.
+///
+/// The policy applies to the files in the directory of the target file whose names match `pattern`.
+/// The target file itself is never deleted, but its size counts towards `max_total_size`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+	/// Delete files last modified longer ago than this.
+	pub max_age: Option<Duration>,
+	/// Delete the oldest files until the total size is at most this many bytes.
+	pub max_total_size: Option<u64>,
+	/// Glob matching the names of the files the policy applies to, `*` matches any characters and `?` one.
+	///
+	/// Defaults to the file name of the target and the same name followed by any `.` suffix, such as `app.log.1`.
+	/// For targets created from a template, `{date}` in the file name matches any date. `{pid}` only matches
+	/// this process, so that the live files of other instances are never deleted.
+	pub pattern: Option<String>,
+	/// Enforce the policy periodically, not only when it is set.
+	pub interval: Option<Duration>
+}
+impl RetentionPolicy {
+	pub const NONE: RetentionPolicy = RetentionPolicy {
+		max_age: None,
+		max_total_size: None,
+		pattern: None,
+		interval: None
+	};
+
+	pub const fn with_max_age(mut self, max_age: Duration) -> Self {
+		self.max_age = Some(max_age);
+		self
+	}
+
+	/// Sets the max age in days.
+	pub const fn with_max_age_days(self, days: u64) -> Self {
+		self.with_max_age(Duration::from_secs(days * 86400))
+	}
+
+	pub const fn with_max_total_size(mut self, max_total_size: u64) -> Self {
+		self.max_total_size = Some(max_total_size);
+		self
+	}
+
+	pub fn with_pattern(mut self, pattern: impl Into<String>) -> Self {
+		self.pattern = Some(pattern.into());
+		self
+	}
+
+	pub const fn with_interval(mut self, interval: Duration) -> Self {
+		self.interval = Some(interval);
+		self
+	}
+
+	/// Deletes the files around `path` that are too old or over the quota.
+	///
+	/// `target_pattern` is the glob of the file names of the target, used when the policy has no `pattern`.
+	fn enforce(&self, path: &Path, target_pattern: &str) -> io::Result<()> {
+		let directory = match path.parent() {
+			Some(parent) if !parent.as_os_str().is_empty() => parent,
+			_ => Path::new(".")
+		};
+		let current = path.file_name();
+		let rotated_pattern = format!("{}.*", target_pattern);
+		let matches = |name: &str| match self.pattern {
+			Some(ref pattern) => glob_matches(pattern, name),
+			None => glob_matches(target_pattern, name) || glob_matches(&rotated_pattern, name)
+		};
+
+		let mut total_size = 0;
+		let mut files = Vec::new();
+		for entry in fs::read_dir(directory)? {
+			let entry = entry?;
+			let name = entry.file_name();
+			if !matches(&name.to_string_lossy()) {
+				continue
+			}
+
+			let metadata = entry.metadata()?;
+			if !metadata.is_file() {
+				continue
+			}
+
+			total_size += metadata.len();
+			if Some(name.as_os_str()) != current {
+				files.push((metadata.modified()?, metadata.len(), entry.path()));
+			}
+		}
+		files.sort_by_key(|&(modified, ..)| modified);
+
+		let now = SystemTime::now();
+		for (modified, size, path) in files {
+			let expired = self
+				.max_age
+				.is_some_and(|max_age| now.duration_since(modified).is_ok_and(|age| age > max_age));
+			let over_quota = self
+				.max_total_size
+				.is_some_and(|max_total_size| total_size > max_total_size);
+			if !expired && !over_quota {
+				continue
+			}
+
+			match fs::remove_file(&path) {
+				Ok(()) => (),
+				Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+				Err(err) => return Err(err)
+			}
+			total_size -= size;
+		}
+
+		Ok(())
+	}
+}
+impl Default for RetentionPolicy {
+	fn default() -> Self {
+		RetentionPolicy::NONE
+	}
+}
+
+/// Background thread running a task every interval, until it is stopped or the task returns `false`.
+struct Periodic {
+	stop: Arc<(Mutex<bool>, Condvar)>,
+	thread: JoinHandle<()>
+}
+impl Periodic {
+	fn spawn(
+		name: &str,
+		interval: Duration,
+		mut task: impl FnMut() -> bool + Send + 'static
+	) -> io::Result<Self> {
+		let stop = Arc::new((Mutex::new(false), Condvar::new()));
+
+		let thread_stop = stop.clone();
+		let thread = thread::Builder::new().name(name.into()).spawn(move || {
+			let (stopped, condvar) = &*thread_stop;
+			let mut stopped = stopped.lock().unwrap_or_else(PoisonError::into_inner);
+
+			while !*stopped {
+				stopped = condvar
+					.wait_timeout(stopped, interval)
+					.unwrap_or_else(PoisonError::into_inner)
+					.0;
+
+				if !*stopped && !task() {
+					break
+				}
+			}
+		})?;
+
+		Ok(Periodic { stop, thread })
+	}
+
+	fn stop(worker: &Mutex<Option<Periodic>>) {
+		let worker = worker.lock().unwrap_or_else(PoisonError::into_inner).take();
+		if let Some(Periodic { stop, thread }) = worker {
+			let (stopped, condvar) = &*stop;
+			*stopped.lock().unwrap_or_else(PoisonError::into_inner) = true;
+			condvar.notify_one();
+
+			let _ = thread.join();
+		}
+	}
+}
 
 pub struct FileTarget {
 	level: Level,
 	ignore_list: IgnoreList<'static>,
-	file: Mutex<File>
+	pattern: Option<Pattern>,
+	fields: LineFields,
+	timestamps: TimestampFormatter,
+	output: Arc<Mutex<FileOutput>>,
+	/// Glob matching the file names of this target, the default of `RetentionPolicy::pattern`.
+	file_pattern: String,
+	flusher: Mutex<Option<Periodic>>,
+	retention: Mutex<Option<Periodic>>,
+	#[cfg(all(unix, feature = "sighup_reopen"))]
+	sighup: Option<(Arc<AtomicBool>, signal_hook::SigId)>
 }
 impl FileTarget {
 	pub fn new(
@@ -24,20 +580,259 @@ impl FileTarget {
 		path: &Path,
 		ignore_patterns: IgnoreListPatterns<'static>
 	) -> io::Result<Self> {
-		let file = Mutex::new(
-			OpenOptions::new()
-				.write(true)
-				.append(true)
-				.create(true)
-				.open(path)?
-		);
+		Self::open(level, path, ignore_patterns, FileOptions::DEFAULT)
+	}
+
+	/// Creates a target writing to a path expanded from a template, see [`expand_path_template`](fn.expand_path_template.html).
+	///
+	/// ```no_run
+	/// use edwardium_logger::targets::file::{FileOptions, FileTarget};
+	///
+	/// let target = FileTarget::from_template(
+	/// 	log::Level::Info,
+	/// 	"logs/{hostname}/{exe}-{date}.log",
+	/// 	Default::default(),
+	/// 	FileOptions::DEFAULT
+	/// 		.with_create_dirs(true)
+	/// 		.with_mode(0o640)
+	/// 		.with_lock(true)
+	/// )?;
+	/// # Ok::<(), std::io::Error>(())
+	/// ```
+	pub fn from_template(
+		level: Level,
+		template: &str,
+		ignore_patterns: IgnoreListPatterns<'static>,
+		options: FileOptions
+	) -> io::Result<Self> {
+		let mut target = Self::open(
+			level,
+			&expand_path_template(template)?,
+			ignore_patterns,
+			options
+		)?;
+		if let Some(name) = Path::new(&expand_template(template, true)?).file_name() {
+			target.file_pattern = name.to_string_lossy().into_owned();
+		}
+
+		Ok(target)
+	}
+
+	pub fn open(
+		level: Level,
+		path: &Path,
+		ignore_patterns: IgnoreListPatterns<'static>,
+		options: FileOptions
+	) -> io::Result<Self> {
+		let file = options.open(path, options.truncate)?;
+		let file_id = file_id(&file.metadata()?);
 
 		Ok(FileTarget {
 			level,
 			ignore_list: IgnoreList::new(ignore_patterns),
-			file
+			pattern: None,
+			fields: LineFields::NONE,
+			timestamps: TimestampFormatter::new(TimestampFormat::DEFAULT),
+			output: Arc::new(Mutex::new(FileOutput {
+				writer: BufWriter::with_capacity(0, file),
+				path: path.to_path_buf(),
+				options,
+				file_id,
+				policy: FlushPolicy::UNBUFFERED,
+				flush_each: false,
+				reopen_check_interval: None,
+				last_reopen_check: Instant::now()
+			})),
+			file_pattern: path
+				.file_name()
+				.map(|name| name.to_string_lossy().into_owned())
+				.unwrap_or_default(),
+			flusher: Mutex::new(None),
+			retention: Mutex::new(None),
+			#[cfg(all(unix, feature = "sighup_reopen"))]
+			sighup: None
 		})
 	}
+
+	/// Sets the optional record fields included in lines, none by default.
+	pub fn with_fields(mut self, fields: LineFields) -> Self {
+		self.fields = fields;
+		self
+	}
+
+	/// Sets how the timestamps are rendered, `TimestampFormat::DEFAULT` by default.
+	///
+	/// Note that files written in delta mode are still readable by the [`parse`](../../../parse/index.html) module,
+	/// but the parsed durations are relative to the previous entry.
+	pub fn with_timestamp_format(mut self, format: TimestampFormat) -> Self {
+		self.timestamps = TimestampFormatter::new(format);
+		self
+	}
+
+	/// Sets the line layout, the default `LogLine` layout is used otherwise.
+	///
+	/// Note that files written using a custom pattern can't be read back using the [`parse`](../../../parse/index.html) module.
+	pub fn with_pattern(mut self, pattern: Pattern) -> Self {
+		self.pattern = Some(pattern);
+		self
+	}
+
+	/// Sets when lines are written to the file, `FlushPolicy::UNBUFFERED` by default.
+	///
+	/// With an `interval`, a background thread is started that writes the buffer periodically.
+	/// Buffered lines are written when the target is flushed, shut down or dropped.
+	///
+	/// ```no_run
+	/// use std::{path::Path, time::Duration};
+	///
+	/// use edwardium_logger::targets::file::{FileTarget, FlushPolicy, SyncMode};
+	///
+	/// let target = FileTarget::new(log::Level::Info, Path::new("app.log"), Default::default())?
+	/// 	.with_flush_policy(
+	/// 		FlushPolicy::buffered(64 * 1024)
+	/// 			.with_interval(Duration::from_millis(500))
+	/// 			.with_flush_level(log::Level::Warn)
+	/// 			.with_sync_on_error(SyncMode::Data)
+	/// 	)?;
+	/// # Ok::<(), std::io::Error>(())
+	/// ```
+	pub fn with_flush_policy(self, policy: FlushPolicy) -> io::Result<Self> {
+		Periodic::stop(&self.flusher);
+
+		{
+			let mut output = self.lock()?;
+			output.writer.flush()?;
+
+			let file = output.writer.get_ref().try_clone()?;
+			output.writer = BufWriter::with_capacity(policy.buffer_size, file);
+			output.policy = policy;
+			output.flush_each = false;
+		}
+
+		if let Some(interval) = policy.interval {
+			let output = Arc::downgrade(&self.output);
+			let flusher = Periodic::spawn("file-target-flusher", interval, move || {
+				let Some(output) = output.upgrade() else {
+					return false
+				};
+				let mut output = output.lock().unwrap_or_else(PoisonError::into_inner);
+				// errors are reported by the next write or flush, the data stays in the buffer
+				let _ = output.writer.flush();
+				true
+			})?;
+			*self.flusher.lock().unwrap_or_else(PoisonError::into_inner) = Some(flusher);
+		}
+
+		Ok(self)
+	}
+
+	/// Sets which old files in the directory of the target file are deleted.
+	///
+	/// The policy is enforced right away and then every `interval` by a background thread.
+	///
+	/// ```no_run
+	/// use std::{path::Path, time::Duration};
+	///
+	/// use edwardium_logger::targets::file::{FileTarget, RetentionPolicy};
+	///
+	/// let target = FileTarget::new(
+	/// 	log::Level::Info,
+	/// 	Path::new("logs/app.log"),
+	/// 	Default::default()
+	/// )?
+	/// .with_retention(
+	/// 	RetentionPolicy::NONE
+	/// 		.with_max_age_days(7)
+	/// 		.with_max_total_size(64 * 1024 * 1024)
+	/// 		.with_interval(Duration::from_secs(3600))
+	/// )?;
+	/// # Ok::<(), std::io::Error>(())
+	/// ```
+	pub fn with_retention(self, policy: RetentionPolicy) -> io::Result<Self> {
+		Periodic::stop(&self.retention);
+
+		let path = self.lock()?.path.clone();
+		let file_pattern = self.file_pattern.clone();
+		policy.enforce(&path, &file_pattern)?;
+
+		if let Some(interval) = policy.interval {
+			let output = Arc::downgrade(&self.output);
+			let retention = Periodic::spawn("file-target-retention", interval, move || {
+				let Some(output) = output.upgrade() else {
+					return false
+				};
+				let path = output
+					.lock()
+					.unwrap_or_else(PoisonError::into_inner)
+					.path
+					.clone();
+				drop(output);
+
+				// errors are transient here, such as files deleted concurrently, the next run tries again
+				let _ = policy.enforce(&path, &file_pattern);
+				true
+			})?;
+			*self
+				.retention
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner) = Some(retention);
+		}
+
+		Ok(self)
+	}
+
+	/// Makes the target check every `interval` whether its path still points to the open file and reopen it if not.
+	///
+	/// This handles external rotation such as `logrotate` in `create` mode without any signal. Replaced files
+	/// are only detected on Unix, on other platforms only a missing file is recreated.
+	pub fn with_reopen_check(self, interval: Duration) -> Self {
+		self.output
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.reopen_check_interval = Some(interval);
+		self
+	}
+
+	/// Makes the target reopen its file when the process receives `SIGHUP`.
+	///
+	/// The file is reopened by the next write. Note that this replaces the default action of `SIGHUP`,
+	/// which terminates the process.
+	#[cfg(all(unix, feature = "sighup_reopen"))]
+	pub fn with_reopen_on_sighup(mut self) -> io::Result<Self> {
+		if self.sighup.is_none() {
+			let flag = Arc::new(AtomicBool::new(false));
+			let id = signal_hook::flag::register(signal_hook::consts::SIGHUP, flag.clone())?;
+			self.sighup = Some((flag, id));
+		}
+
+		Ok(self)
+	}
+
+	/// Writes the buffered lines and opens the file at the target path again, creating it if it doesn't exist.
+	///
+	/// Call this after the file was moved away, for example by `logrotate`, so that the target writes to a new file
+	/// at the original path instead of the moved one.
+	pub fn reopen(&self) -> io::Result<()> {
+		self.lock()?.reopen()
+	}
+
+	fn lock(&self) -> io::Result<MutexGuard<'_, FileOutput>> {
+		self.output
+			.lock()
+			.map_err(|_| io::Error::other("mutex poison error"))
+	}
+
+	fn line_layout(&self, duration_since_start: Duration) -> Layout<'_> {
+		Layout {
+			style: match self.pattern {
+				Some(ref pattern) => LayoutStyle::Pattern(pattern),
+				None => LayoutStyle::Plain
+			},
+			timestamp: self.timestamps.duration(duration_since_start),
+			timestamp_format: self.timestamps.format(),
+			fields: self.fields
+		}
+	}
 }
 impl Target for FileTarget {
 	type Error = io::Error;
@@ -46,24 +841,406 @@ impl Target for FileTarget {
 		self.level
 	}
 
-	fn ignore(&self, record: &Record) -> bool {
-		self.ignore_list.ignore(record)
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		metadata.level() <= self.level && !self.ignore_list.ignore_target(metadata.target())
 	}
 
 	fn write(&self, duration_since_start: Duration, record: &Record) -> Result<(), Self::Error> {
-		match self.file.lock() {
-			Err(_) => Err(io::Error::new(io::ErrorKind::Other, "mutex poison error")),
-			Ok(mut lock) => {
-				let log_line = super::util::LogLine::new(duration_since_start.into(), record);
-				writeln!(&mut lock, "{}", log_line)
+		layout::write_with_layout(self.line_layout(duration_since_start), record, |line| {
+			self.write_rendered(duration_since_start, record, line)
+		})
+	}
+
+	fn layout(&self, duration_since_start: Duration) -> Option<Layout<'_>> {
+		Some(self.line_layout(duration_since_start))
+	}
+
+	fn write_rendered(
+		&self,
+		_duration_since_start: Duration,
+		record: &Record,
+		line: &[u8]
+	) -> Result<(), Self::Error> {
+		let mut output = self.lock()?;
+
+		#[cfg(all(unix, feature = "sighup_reopen"))]
+		if let Some((ref flag, _)) = self.sighup {
+			if flag.load(Ordering::Relaxed) && flag.swap(false, Ordering::Relaxed) {
+				output.reopen()?;
 			}
 		}
+
+		output.write(record.level(), line)
 	}
 
 	fn flush(&self) -> Result<(), Self::Error> {
-		match self.file.lock() {
-			Err(_) => Err(io::Error::new(io::ErrorKind::Other, "mutex poison error")),
-			Ok(mut lock) => lock.flush()
+		self.lock()?.writer.flush()
+	}
+
+	/// Stops the background threads and writes the buffer, records written afterwards are not buffered anymore.
+	fn shutdown(&self) -> Result<(), Self::Error> {
+		Periodic::stop(&self.flusher);
+		Periodic::stop(&self.retention);
+
+		let mut output = self.lock()?;
+		output.flush_each = true;
+		output.writer.flush()
+	}
+}
+impl Drop for FileTarget {
+	fn drop(&mut self) {
+		Periodic::stop(&self.flusher);
+		Periodic::stop(&self.retention);
+
+		#[cfg(all(unix, feature = "sighup_reopen"))]
+		if let Some((_, id)) = self.sighup.take() {
+			signal_hook::low_level::unregister(id);
 		}
 	}
 }
+
+/// Returns an identity of a file used to detect that the file at a path was replaced.
+#[cfg(unix)]
+pub fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+	use std::os::unix::fs::MetadataExt;
+
+	Some((metadata.dev(), metadata.ino()))
+}
+#[cfg(not(unix))]
+pub fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		fs::{self, File},
+		path::{Path, PathBuf},
+		time::{Duration, SystemTime}
+	};
+
+	use log::{Level, Record};
+
+	use super::{glob_matches, FileOptions, FileTarget, FlushPolicy, RetentionPolicy, SyncMode};
+	use crate::{target::Target, targets::test_util::test_dir};
+
+	/// Creates a file of `size` bytes last modified `age` ago.
+	fn create(path: &Path, size: usize, age: Duration) {
+		fs::write(path, vec![b'x'; size]).unwrap();
+		File::options()
+			.write(true)
+			.open(path)
+			.unwrap()
+			.set_modified(SystemTime::now() - age)
+			.unwrap();
+	}
+
+	fn names(dir: &Path) -> Vec<String> {
+		let mut names: Vec<String> = fs::read_dir(dir)
+			.unwrap()
+			.map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+			.collect();
+		names.sort();
+
+		names
+	}
+
+	const DAY: Duration = Duration::from_secs(86400);
+
+	fn log(target: &FileTarget, level: Level, message: &str) {
+		target
+			.write(
+				Duration::ZERO,
+				&Record::builder()
+					.level(level)
+					.args(format_args!("{}", message))
+					.build()
+			)
+			.unwrap();
+	}
+
+	/// Returns the messages written to the file at `path`.
+	fn messages(path: &Path) -> Vec<String> {
+		fs::read_to_string(path)
+			.unwrap()
+			.lines()
+			.map(|line| line.rsplit_once(") ").unwrap().1.to_string())
+			.collect()
+	}
+
+	fn buffered_target(name: &str, policy: FlushPolicy) -> (FileTarget, PathBuf) {
+		let path = test_dir(name).join("app.log");
+		let target = FileTarget::new(Level::Trace, &path, Default::default())
+			.unwrap()
+			.with_flush_policy(policy)
+			.unwrap();
+
+		(target, path)
+	}
+
+	#[test]
+	fn glob() {
+		assert!(glob_matches("app.log", "app.log"));
+		assert!(!glob_matches("app.log", "app.log.1"));
+		assert!(glob_matches("app.log.*", "app.log.1"));
+		assert!(glob_matches("app-*.log", "app-2026-10-18.log"));
+		assert!(glob_matches("app-*.log", "app-.log"));
+		assert!(!glob_matches("app-*.log", "app-2026-10-18.log.gz"));
+		assert!(glob_matches("*a*b?", "xaxxbbc"));
+		assert!(!glob_matches("a?", "a"));
+	}
+
+	#[test]
+	fn retention_max_age_keeps_unrelated_files() {
+		let dir = test_dir("retention-age");
+		for name in ["app.log.1", "app.toml", "application.db", "app"] {
+			create(&dir.join(name), 10, 3 * DAY);
+		}
+		fs::create_dir(dir.join("app.log.d")).unwrap();
+		create(&dir.join("app.log.2"), 10, Duration::ZERO);
+
+		let target = FileTarget::new(Level::Info, &dir.join("app.log"), Default::default())
+			.unwrap()
+			.with_retention(RetentionPolicy::NONE.with_max_age_days(1))
+			.unwrap();
+		drop(target);
+
+		assert_eq!(
+			names(&dir),
+			[
+				"app",
+				"app.log",
+				"app.log.2",
+				"app.log.d",
+				"app.toml",
+				"application.db"
+			]
+		);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn retention_max_total_size_deletes_oldest() {
+		let dir = test_dir("retention-size");
+		create(&dir.join("app.log.1"), 100, DAY);
+		create(&dir.join("app.log.2"), 100, 2 * DAY);
+		create(&dir.join("app.log.3"), 100, 3 * DAY);
+		create(&dir.join("other.log"), 1000, 4 * DAY);
+		create(&dir.join("app.log"), 50, Duration::ZERO);
+
+		let target = FileTarget::new(Level::Info, &dir.join("app.log"), Default::default())
+			.unwrap()
+			.with_retention(RetentionPolicy::NONE.with_max_total_size(260))
+			.unwrap();
+		drop(target);
+
+		assert_eq!(
+			names(&dir),
+			["app.log", "app.log.1", "app.log.2", "other.log"]
+		);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn retention_matches_templated_files() {
+		let dir = test_dir("retention-template");
+		create(&dir.join("app-2020-01-01.log"), 10, 3 * DAY);
+		create(&dir.join("app-2020-01-02.log.gz"), 10, 3 * DAY);
+		create(&dir.join("app-notes.txt"), 10, 3 * DAY);
+		create(&dir.join("app.log"), 10, 3 * DAY);
+
+		let template = format!("{}/app-{{date}}.log", dir.display());
+		let target = FileTarget::from_template(
+			Level::Info,
+			&template,
+			Default::default(),
+			FileOptions::DEFAULT
+		)
+		.unwrap()
+		.with_retention(RetentionPolicy::NONE.with_max_age_days(1))
+		.unwrap();
+		let current = target.lock().unwrap().path.clone();
+		drop(target);
+
+		let mut expected = vec![
+			"app-notes.txt".to_string(),
+			"app.log".to_string(),
+			current.file_name().unwrap().to_string_lossy().into_owned(),
+		];
+		expected.sort();
+		assert_eq!(names(&dir), expected);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn retention_keeps_files_of_other_processes() {
+		let dir = test_dir("retention-pid");
+		// live file of another instance, not written to for a while
+		create(&dir.join("app-1.log"), 10, 3 * DAY);
+		let other =
+			FileTarget::new(Level::Info, &dir.join("app-1.log"), Default::default()).unwrap();
+
+		let template = format!("{}/app-{{pid}}.log", dir.display());
+		let target = FileTarget::from_template(
+			Level::Info,
+			&template,
+			Default::default(),
+			FileOptions::DEFAULT
+		)
+		.unwrap()
+		.with_retention(
+			RetentionPolicy::NONE
+				.with_max_age_days(1)
+				.with_max_total_size(1)
+		)
+		.unwrap();
+		drop(target);
+
+		other
+			.write(
+				Duration::ZERO,
+				&Record::builder()
+					.level(Level::Info)
+					.args(format_args!("still here"))
+					.build()
+			)
+			.unwrap();
+		drop(other);
+
+		assert!(fs::read_to_string(dir.join("app-1.log"))
+			.unwrap()
+			.ends_with("still here\n"));
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn retention_explicit_pattern() {
+		let dir = test_dir("retention-pattern");
+		create(&dir.join("app.log.1"), 10, 3 * DAY);
+		create(&dir.join("trace-1.bin"), 10, 3 * DAY);
+
+		let target = FileTarget::new(Level::Info, &dir.join("app.log"), Default::default())
+			.unwrap()
+			.with_retention(
+				RetentionPolicy::NONE
+					.with_max_age_days(1)
+					.with_pattern("trace-?.bin")
+			)
+			.unwrap();
+		drop(target);
+
+		assert_eq!(names(&dir), ["app.log", "app.log.1"]);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn lock_is_exclusive() {
+		let dir = test_dir("lock");
+		let path = dir.join("app.log");
+		let options = FileOptions::DEFAULT.with_lock(true);
+
+		let file = options.open(&path, false).unwrap();
+		let err = options.open(&path, true).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+
+		drop(file);
+		options.open(&path, true).unwrap();
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn flush_level_writes_buffer() {
+		let (target, path) = buffered_target(
+			"flush-level",
+			FlushPolicy::buffered(1024).with_flush_level(Level::Warn)
+		);
+
+		log(&target, Level::Info, "a");
+		assert!(messages(&path).is_empty());
+		log(&target, Level::Warn, "b");
+		assert_eq!(messages(&path), ["a", "b"]);
+
+		fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn full_buffer_is_written() {
+		let (target, path) = buffered_target("buffer-size", FlushPolicy::buffered(48));
+
+		// each line is 26 bytes, the second one doesn't fit anymore
+		log(&target, Level::Info, "a");
+		assert!(messages(&path).is_empty());
+		log(&target, Level::Info, "b");
+		assert_eq!(messages(&path), ["a"]);
+
+		fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn sync_on_error_writes_buffer() {
+		let (target, path) = buffered_target(
+			"sync-on-error",
+			FlushPolicy::buffered(1024).with_sync_on_error(SyncMode::Data)
+		);
+
+		log(&target, Level::Warn, "a");
+		assert!(messages(&path).is_empty());
+		log(&target, Level::Error, "b");
+		assert_eq!(messages(&path), ["a", "b"]);
+
+		fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn interval_writes_buffer() {
+		let (target, path) = buffered_target(
+			"flush-interval",
+			FlushPolicy::buffered(1024).with_interval(Duration::from_millis(20))
+		);
+
+		log(&target, Level::Info, "a");
+		let start = std::time::Instant::now();
+		while messages(&path).is_empty() {
+			assert!(
+				start.elapsed() < Duration::from_secs(5),
+				"buffer was not written"
+			);
+			std::thread::sleep(Duration::from_millis(10));
+		}
+		assert_eq!(messages(&path), ["a"]);
+
+		drop(target);
+		fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn shutdown_stops_buffering() {
+		let (target, path) = buffered_target("shutdown", FlushPolicy::buffered(1024));
+
+		log(&target, Level::Info, "a");
+		assert!(messages(&path).is_empty());
+		target.shutdown().unwrap();
+		assert_eq!(messages(&path), ["a"]);
+		log(&target, Level::Info, "b");
+		assert_eq!(messages(&path), ["a", "b"]);
+
+		fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn reopen_check_follows_rename() {
+		let (target, path) = buffered_target("reopen-check", FlushPolicy::buffered(1024));
+		let target = target.with_reopen_check(Duration::ZERO);
+		let rotated = path.with_extension("log.1");
+
+		log(&target, Level::Info, "a");
+		fs::rename(&path, &rotated).unwrap();
+		log(&target, Level::Info, "b");
+		target.flush().unwrap();
+
+		assert_eq!(messages(&rotated), ["a"]);
+		assert_eq!(messages(&path), ["b"]);
+		fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+}
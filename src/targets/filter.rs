@@ -0,0 +1,274 @@
+//! Filtering of the records written to a target by level.
+//!
+//! [`Target::level`](../../target/trait.Target.html#tymethod.level) only limits the verbosity of a target.
+//! Wrapping a target in [`LevelFiltered`](struct.LevelFiltered.html) restricts it to any set of levels,
+//! for example to split errors and the rest into two files:
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use edwardium_logger::targets::{
+//! 	file::FileTarget,
+//! 	filter::{LevelFiltered, LevelSet}
+//! };
+//! use log::Level;
+//!
+//! let targets = (
+//! 	FileTarget::new(Level::Error, Path::new("error.log"), Default::default())?,
+//! 	LevelFiltered::new(
+//! 		FileTarget::new(Level::Info, Path::new("info.log"), Default::default())?,
+//! 		LevelSet::between(Level::Warn, Level::Info)
+//! 	)
+//! );
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::time::Duration;
+
+use log::{Level, Metadata, Record};
+
+use crate::target::Target;
+
+#[cfg(feature = "std")]
+use super::util::layout::Layout;
+
+/// Set of log levels.
+///
+/// ```
+/// use edwardium_logger::targets::filter::LevelSet;
+/// use log::Level;
+///
+/// let levels = LevelSet::between(Level::Warn, Level::Info);
+/// assert!(levels.contains(Level::Warn) && levels.contains(Level::Info));
+/// assert!(!levels.contains(Level::Error) && !levels.contains(Level::Debug));
+///
+/// let levels = LevelSet::only(Level::Error).with(Level::Debug);
+/// assert_eq!(levels.most_verbose(), Some(Level::Debug));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LevelSet(u8);
+impl LevelSet {
+	pub const ALL: LevelSet = LevelSet::NONE
+		.with(Level::Error)
+		.with(Level::Warn)
+		.with(Level::Info)
+		.with(Level::Debug)
+		.with(Level::Trace);
+	pub const NONE: LevelSet = LevelSet(0);
+
+	const fn bit(level: Level) -> u8 {
+		1 << (level as usize)
+	}
+
+	pub const fn only(level: Level) -> Self {
+		LevelSet::NONE.with(level)
+	}
+
+	/// Levels from `most_severe` to `least_severe`, both inclusive.
+	pub const fn between(most_severe: Level, least_severe: Level) -> Self {
+		let mut set = LevelSet::NONE;
+		let mut index = most_severe as usize;
+		while index <= least_severe as usize {
+			set.0 |= 1 << index;
+			index += 1;
+		}
+
+		set
+	}
+
+	/// `level` and all levels more severe than it, the same levels a target with `level` writes.
+	pub const fn up_to(level: Level) -> Self {
+		LevelSet::between(Level::Error, level)
+	}
+
+	/// `level` and all levels less severe than it.
+	pub const fn down_from(level: Level) -> Self {
+		LevelSet::between(level, Level::Trace)
+	}
+
+	pub const fn with(mut self, level: Level) -> Self {
+		self.0 |= Self::bit(level);
+		self
+	}
+
+	pub const fn without(mut self, level: Level) -> Self {
+		self.0 &= !Self::bit(level);
+		self
+	}
+
+	pub const fn contains(&self, level: Level) -> bool {
+		self.0 & Self::bit(level) != 0
+	}
+
+	/// Returns the least severe level in the set.
+	pub fn most_verbose(&self) -> Option<Level> {
+		[
+			Level::Trace,
+			Level::Debug,
+			Level::Info,
+			Level::Warn,
+			Level::Error
+		]
+		.into_iter()
+		.find(|&level| self.contains(level))
+	}
+}
+impl Default for LevelSet {
+	fn default() -> Self {
+		LevelSet::ALL
+	}
+}
+
+/// Target that only writes records with a level in `levels`.
+pub struct LevelFiltered<T: Target> {
+	target: T,
+	levels: LevelSet
+}
+impl<T: Target> LevelFiltered<T> {
+	pub const fn new(target: T, levels: LevelSet) -> Self {
+		LevelFiltered { target, levels }
+	}
+
+	pub fn target(&self) -> &T {
+		&self.target
+	}
+
+	pub fn levels(&self) -> LevelSet {
+		self.levels
+	}
+}
+impl<T: Target> Target for LevelFiltered<T> {
+	type Error = T::Error;
+
+	fn level(&self) -> Level {
+		match self.levels.most_verbose() {
+			Some(level) => level.min(self.target.level()),
+			None => Level::Error
+		}
+	}
+
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		self.levels.contains(metadata.level()) && self.target.enabled(metadata)
+	}
+
+	fn ignore(&self, record: &Record) -> bool {
+		self.target.ignore(record)
+	}
+
+	fn write(&self, duration_since_start: Duration, record: &Record) -> Result<(), Self::Error> {
+		self.target.write(duration_since_start, record)
+	}
+
+	#[cfg(feature = "std")]
+	fn layout(&self, duration_since_start: Duration) -> Option<Layout<'_>> {
+		self.target.layout(duration_since_start)
+	}
+
+	#[cfg(feature = "std")]
+	fn write_rendered(
+		&self,
+		duration_since_start: Duration,
+		record: &Record,
+		line: &[u8]
+	) -> Result<(), Self::Error> {
+		self.target
+			.write_rendered(duration_since_start, record, line)
+	}
+
+	fn flush(&self) -> Result<(), Self::Error> {
+		self.target.flush()
+	}
+
+	fn shutdown(&self) -> Result<(), Self::Error> {
+		self.target.shutdown()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{fmt, time::Duration};
+
+	use log::{Level, Metadata, Record};
+
+	use super::{LevelFiltered, LevelSet};
+	use crate::target::Target;
+
+	struct Sink(Level);
+	impl Target for Sink {
+		type Error = fmt::Error;
+
+		fn level(&self) -> Level {
+			self.0
+		}
+
+		fn write(
+			&self,
+			_duration_since_start: Duration,
+			_record: &Record
+		) -> Result<(), fmt::Error> {
+			Ok(())
+		}
+
+		fn flush(&self) -> Result<(), fmt::Error> {
+			Ok(())
+		}
+	}
+
+	fn enabled(target: &impl Target, level: Level) -> bool {
+		target.enabled(&Metadata::builder().level(level).build())
+	}
+
+	#[test]
+	fn level_set() {
+		assert_eq!(LevelSet::NONE.most_verbose(), None);
+		assert_eq!(LevelSet::ALL.most_verbose(), Some(Level::Trace));
+		assert_eq!(
+			LevelSet::up_to(Level::Info),
+			LevelSet::between(Level::Error, Level::Info)
+		);
+		assert_eq!(
+			LevelSet::down_from(Level::Debug),
+			LevelSet::only(Level::Debug).with(Level::Trace)
+		);
+		assert_eq!(LevelSet::between(Level::Info, Level::Warn), LevelSet::NONE);
+		assert_eq!(
+			LevelSet::ALL.without(Level::Trace),
+			LevelSet::up_to(Level::Debug)
+		);
+		assert_eq!(
+			LevelSet::only(Level::Error)
+				.with(Level::Debug)
+				.most_verbose(),
+			Some(Level::Debug)
+		);
+	}
+
+	#[test]
+	fn enabled_requires_set_and_target() {
+		let target = LevelFiltered::new(
+			Sink(Level::Debug),
+			LevelSet::only(Level::Error).with(Level::Trace)
+		);
+
+		assert!(enabled(&target, Level::Error));
+		assert!(!enabled(&target, Level::Warn));
+		// in the set, but more verbose than the target
+		assert!(!enabled(&target, Level::Trace));
+	}
+
+	#[test]
+	fn level_is_the_less_verbose_of_set_and_target() {
+		let level = |target_level, levels| LevelFiltered::new(Sink(target_level), levels).level();
+
+		assert_eq!(
+			level(Level::Trace, LevelSet::up_to(Level::Warn)),
+			Level::Warn
+		);
+		assert_eq!(level(Level::Info, LevelSet::ALL), Level::Info);
+		assert_eq!(
+			level(Level::Info, LevelSet::only(Level::Error)),
+			Level::Error
+		);
+		assert_eq!(level(Level::Trace, LevelSet::NONE), Level::Error);
+	}
+}
@@ -2,12 +2,21 @@
 
 pub mod util;
 
+pub mod filter;
+
+#[cfg(target_has_atomic = "32")]
+pub mod fallback;
+
 #[cfg(feature = "file_target")]
 pub mod file;
+#[cfg(feature = "file_target")]
+pub mod routing;
 #[cfg(feature = "stderr_target")]
 pub mod stderr;
 #[cfg(feature = "stdout_target")]
 pub mod stdout;
+#[cfg(all(test, feature = "file_target"))]
+mod test_util;
 
 #[cfg(feature = "uart_target")]
 pub mod uart;
@@ -0,0 +1,471 @@
+//! File target writing records to different files depending on the record.
+
+use std::{
+	borrow::Cow,
+	io,
+	path::PathBuf,
+	sync::{Arc, Mutex, MutexGuard, PoisonError},
+	time::Duration
+};
+
+use log::{Level, Metadata, Record};
+
+use crate::target::Target;
+
+use super::file::{expand_path_template, FileOptions, FileTarget};
+
+enum Matcher {
+	TargetPrefix(String),
+	#[cfg(feature = "kv")]
+	Key(String)
+}
+
+/// Rule sending matching records to a file.
+///
+/// The path is a template as in [`expand_path_template`](../file/fn.expand_path_template.html) that can also
+/// contain `{value}`: the record target for target routes and the value of the key for key routes.
+/// The value is sanitized so that it can't point outside of the directory, values that are sanitized into
+/// the same path share one file.
+pub struct Route {
+	matcher: Matcher,
+	path: String,
+	level: Level
+}
+impl Route {
+	/// Matches records whose target is `prefix` or a submodule of it, an empty prefix matches all records.
+	///
+	/// `Route::target_prefix("app::net", "net.log", Level::Debug)` matches `app::net` and `app::net::tcp`,
+	/// but not `app::network`.
+	pub fn target_prefix(prefix: impl Into<String>, path: impl Into<String>, level: Level) -> Self {
+		Route {
+			matcher: Matcher::TargetPrefix(prefix.into()),
+			path: path.into(),
+			level
+		}
+	}
+
+	/// Matches records that have a key-value pair with `key`, for example `log::info!(tenant = id; "...")`.
+	///
+	/// Requires the `kv` feature.
+	#[cfg(feature = "kv")]
+	pub fn key(key: impl Into<String>, path: impl Into<String>, level: Level) -> Self {
+		Route {
+			matcher: Matcher::Key(key.into()),
+			path: path.into(),
+			level
+		}
+	}
+
+	fn matches_target(&self, target: &str) -> bool {
+		match self.matcher {
+			Matcher::TargetPrefix(ref prefix) => match target.strip_prefix(prefix.as_str()) {
+				Some(rest) => prefix.is_empty() || rest.is_empty() || rest.starts_with("::"),
+				None => false
+			},
+			#[cfg(feature = "kv")]
+			Matcher::Key(_) => true
+		}
+	}
+
+	/// Returns the `{value}` of the record if the route matches it.
+	fn value<'r>(&self, record: &'r Record) -> Option<Cow<'r, str>> {
+		match self.matcher {
+			Matcher::TargetPrefix(_) => match self.matches_target(record.target()) {
+				true => Some(Cow::Borrowed(record.target())),
+				false => None
+			},
+			#[cfg(feature = "kv")]
+			Matcher::Key(ref key) => record
+				.key_values()
+				.get(log::kv::Key::from_str(key))
+				.map(|value| Cow::Owned(value.to_string()))
+		}
+	}
+
+	fn path(&self, value: &str) -> io::Result<PathBuf> {
+		let value: String = value
+			.chars()
+			.map(|c| match c {
+				'a' ..= 'z' | 'A' ..= 'Z' | '0' ..= '9' | '-' | '_' => c,
+				_ => '_'
+			})
+			.collect();
+
+		expand_path_template(&self.path.replace("{value}", &value))
+	}
+}
+
+type Configure = dyn Fn(FileTarget) -> io::Result<FileTarget> + Send + Sync;
+
+/// Open file with the routes and values whose path it is.
+///
+/// Different values can be sanitized into the same path, they then share the file.
+struct OpenFile {
+	path: PathBuf,
+	keys: Vec<(usize, String)>,
+	file: Arc<FileTarget>
+}
+
+/// Writes each record to the file of the first route matching it, records matching no route are dropped.
+///
+/// Files are opened when the first record is routed to them. At most `max_open` files are kept open,
+/// the least recently used one is flushed and closed when another one has to be opened.
+///
+/// ```no_run
+/// use edwardium_logger::targets::{
+/// 	file::FileOptions,
+/// 	routing::{Route, RoutingFileTarget}
+/// };
+/// use log::Level;
+///
+/// let target = RoutingFileTarget::new(vec![
+/// 	Route::target_prefix("app::net", "logs/net.log", Level::Debug),
+/// 	// one file per module for everything else
+/// 	Route::target_prefix("", "logs/{value}.log", Level::Info),
+/// ])
+/// .with_options(FileOptions::DEFAULT.with_create_dirs(true))
+/// .with_configure(|target| {
+/// 	Ok(target.with_fields(edwardium_logger::targets::util::LineFields::ALL))
+/// });
+/// ```
+pub struct RoutingFileTarget {
+	routes: Vec<Route>,
+	options: FileOptions,
+	max_open: usize,
+	configure: Option<Box<Configure>>,
+	/// Open files, least recently used first.
+	open: Mutex<Vec<OpenFile>>,
+	/// Held while a file is opened.
+	opening: Mutex<()>
+}
+impl RoutingFileTarget {
+	pub const DEFAULT_MAX_OPEN: usize = 16;
+
+	pub fn new(routes: Vec<Route>) -> Self {
+		RoutingFileTarget {
+			routes,
+			options: FileOptions::DEFAULT,
+			max_open: Self::DEFAULT_MAX_OPEN,
+			configure: None,
+			open: Mutex::new(Vec::new()),
+			opening: Mutex::new(())
+		}
+	}
+
+	/// Sets how the files are opened, `FileOptions::DEFAULT` by default.
+	pub fn with_options(mut self, options: FileOptions) -> Self {
+		self.options = options;
+		self
+	}
+
+	/// Sets the max number of files open at once, `DEFAULT_MAX_OPEN` by default.
+	pub fn with_max_open(mut self, max_open: usize) -> Self {
+		self.max_open = max_open.max(1);
+		self
+	}
+
+	/// Sets a function configuring each newly opened `FileTarget`, for example its fields or flush policy.
+	pub fn with_configure(
+		mut self,
+		configure: impl Fn(FileTarget) -> io::Result<FileTarget> + Send + Sync + 'static
+	) -> Self {
+		self.configure = Some(Box::new(configure));
+		self
+	}
+
+	fn lock_open(&self) -> MutexGuard<'_, Vec<OpenFile>> {
+		self.open.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Returns the open file of `route` and `value`, marking it as the most recently used.
+	fn find(open: &mut Vec<OpenFile>, route: usize, value: &str) -> Option<Arc<FileTarget>> {
+		let index = open
+			.iter()
+			.position(|entry| entry.keys.iter().any(|(r, v)| *r == route && v == value))?;
+		let entry = open.remove(index);
+		let file = entry.file.clone();
+		open.push(entry);
+
+		Some(file)
+	}
+
+	/// Returns the file target of `route` and `value`, opening it if needed, and the file closed to make room for it.
+	///
+	/// The caller shuts the closed file down, it is closed once writes in progress finish.
+	fn file(
+		&self,
+		route: usize,
+		value: &str
+	) -> io::Result<(Arc<FileTarget>, Option<Arc<FileTarget>>)> {
+		if let Some(file) = Self::find(&mut self.lock_open(), route, value) {
+			return Ok((file, None))
+		}
+
+		// files are opened and configured outside of the `open` lock, so that writes to open files don't wait
+		let _opening = self.opening.lock().unwrap_or_else(PoisonError::into_inner);
+		let route_config = &self.routes[route];
+		let path = route_config.path(value)?;
+
+		{
+			let mut open = self.lock_open();
+			// opened by another thread in the meantime
+			if let Some(file) = Self::find(&mut open, route, value) {
+				return Ok((file, None))
+			}
+			// another value with the same path
+			if let Some(index) = open.iter().position(|entry| entry.path == path) {
+				let mut entry = open.remove(index);
+				entry.keys.push((route, value.to_string()));
+				let file = entry.file.clone();
+				open.push(entry);
+
+				return Ok((file, None))
+			}
+		}
+
+		let file = FileTarget::open(route_config.level, &path, Default::default(), self.options)?;
+		let file = Arc::new(match self.configure {
+			Some(ref configure) => configure(file)?,
+			None => file
+		});
+
+		let evicted = {
+			let mut open = self.lock_open();
+			let evicted = match open.len() >= self.max_open {
+				true => Some(open.remove(0).file),
+				false => None
+			};
+			open.push(OpenFile {
+				path,
+				keys: vec![(route, value.to_string())],
+				file: file.clone()
+			});
+
+			evicted
+		};
+
+		Ok((file, evicted))
+	}
+
+	fn each_open(&self, f: impl Fn(&FileTarget) -> io::Result<()>) -> io::Result<()> {
+		let files: Vec<Arc<FileTarget>> = self
+			.lock_open()
+			.iter()
+			.map(|entry| entry.file.clone())
+			.collect();
+
+		let mut result = Ok(());
+		for file in files {
+			let file_result = f(&file);
+			if result.is_ok() {
+				result = file_result;
+			}
+		}
+
+		result
+	}
+}
+impl Target for RoutingFileTarget {
+	type Error = io::Error;
+
+	fn level(&self) -> Level {
+		self.routes
+			.iter()
+			.map(|route| route.level)
+			.max()
+			.unwrap_or(Level::Error)
+	}
+
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		self.routes
+			.iter()
+			.any(|route| metadata.level() <= route.level && route.matches_target(metadata.target()))
+	}
+
+	fn write(&self, duration_since_start: Duration, record: &Record) -> Result<(), Self::Error> {
+		let routed = self
+			.routes
+			.iter()
+			.enumerate()
+			.find_map(|(index, route)| route.value(record).map(|value| (index, value)));
+
+		match routed {
+			Some((route, value)) if record.level() <= self.routes[route].level => {
+				let (file, evicted) = self.file(route, &value)?;
+				let result = file.write(duration_since_start, record);
+
+				// failing to flush the closed file is reported after the record is written
+				match evicted {
+					Some(evicted) => result.and(evicted.shutdown()),
+					None => result
+				}
+			}
+			_ => Ok(())
+		}
+	}
+
+	fn flush(&self) -> Result<(), Self::Error> {
+		self.each_open(|file| file.flush())
+	}
+
+	fn shutdown(&self) -> Result<(), Self::Error> {
+		self.each_open(|file| file.shutdown())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		fs,
+		path::{Path, PathBuf},
+		time::Duration
+	};
+
+	use log::{Level, Record};
+
+	use super::{Route, RoutingFileTarget};
+	use crate::{
+		target::Target,
+		targets::{
+			file::{FileOptions, FlushPolicy},
+			test_util::test_dir
+		}
+	};
+
+	fn per_target(dir: &Path) -> RoutingFileTarget {
+		RoutingFileTarget::new(vec![Route::target_prefix(
+			"",
+			format!("{}/{{value}}.log", dir.display()),
+			Level::Info
+		)])
+	}
+
+	fn write(target: &RoutingFileTarget, record_target: &str, message: &str) {
+		target
+			.write(
+				Duration::ZERO,
+				&Record::builder()
+					.level(Level::Info)
+					.target(record_target)
+					.args(format_args!("{}", message))
+					.build()
+			)
+			.unwrap();
+	}
+
+	fn open_paths(target: &RoutingFileTarget) -> Vec<PathBuf> {
+		target
+			.lock_open()
+			.iter()
+			.map(|entry| entry.path.clone())
+			.collect()
+	}
+
+	#[test]
+	fn evicts_least_recently_used() {
+		let dir = test_dir("routing-evict");
+		let target = per_target(&dir).with_max_open(2);
+
+		write(&target, "a", "1");
+		write(&target, "b", "2");
+		write(&target, "a", "3");
+		write(&target, "c", "4");
+		assert_eq!(open_paths(&target), [dir.join("a.log"), dir.join("c.log")]);
+
+		// the evicted file is opened again in append mode
+		write(&target, "b", "5");
+		assert_eq!(open_paths(&target), [dir.join("c.log"), dir.join("b.log")]);
+		target.flush().unwrap();
+
+		let lines = fs::read_to_string(dir.join("b.log")).unwrap();
+		let messages: Vec<&str> = lines
+			.lines()
+			.map(|line| line.rsplit(' ').next().unwrap())
+			.collect();
+		assert_eq!(messages, ["2", "5"]);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn sanitized_values_share_a_file() {
+		let dir = test_dir("routing-sanitize");
+		let target = per_target(&dir).with_options(FileOptions::DEFAULT.with_lock(true));
+
+		write(&target, "a::b", "1");
+		write(&target, "a__b", "2");
+		write(&target, "../escape", "3");
+		target.flush().unwrap();
+
+		let open = target.lock_open();
+		assert_eq!(open.len(), 2);
+		assert_eq!(open[0].path, dir.join("a__b.log"));
+		assert_eq!(open[0].keys.len(), 2);
+		drop(open);
+
+		let mut names: Vec<String> = fs::read_dir(&dir)
+			.unwrap()
+			.map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+			.collect();
+		names.sort();
+		assert_eq!(names, ["___escape.log", "a__b.log"]);
+		assert_eq!(
+			fs::read_to_string(dir.join("a__b.log"))
+				.unwrap()
+				.lines()
+				.count(),
+			2
+		);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	/// `/dev/full` accepts the buffered record but fails to flush it when the file is closed.
+	#[cfg(target_os = "linux")]
+	#[test]
+	fn eviction_error_does_not_drop_the_record() {
+		let dir = test_dir("routing-evict-error");
+		let target = RoutingFileTarget::new(vec![
+			Route::target_prefix("full", "/dev/full", Level::Info),
+			Route::target_prefix("", format!("{}/{{value}}.log", dir.display()), Level::Info),
+		])
+		.with_max_open(1)
+		.with_configure(|target| target.with_flush_policy(FlushPolicy::buffered(1024)));
+
+		write(&target, "full", "1");
+		let record = Record::builder()
+			.level(Level::Info)
+			.target("app")
+			.args(format_args!("2"))
+			.build();
+		assert!(target.write(Duration::ZERO, &record).is_err());
+		target.flush().unwrap();
+
+		assert_eq!(open_paths(&target), [dir.join("app.log")]);
+		assert!(fs::read_to_string(dir.join("app.log"))
+			.unwrap()
+			.ends_with(" 2\n"));
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn first_matching_route_wins() {
+		let dir = test_dir("routing-routes");
+		let target = RoutingFileTarget::new(vec![
+			Route::target_prefix(
+				"app::net",
+				format!("{}/net.log", dir.display()),
+				Level::Debug
+			),
+			Route::target_prefix("app", format!("{}/app.log", dir.display()), Level::Info),
+		]);
+
+		write(&target, "app::net::tcp", "1");
+		write(&target, "app::network", "2");
+		write(&target, "other", "3");
+
+		assert_eq!(
+			open_paths(&target),
+			[dir.join("net.log"), dir.join("app.log")]
+		);
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
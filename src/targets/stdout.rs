@@ -1,34 +1,120 @@
 use std::{io, io::Write, time::Duration};
 
-use log::{Level, Record};
+use log::{Level, Metadata, Record};
 
 use crate::target::Target;
 
-use super::util::ignore_list::{IgnoreList, IgnoreListPatterns};
+use super::util::{
+	ignore_list::{IgnoreList, IgnoreListPatterns},
+	pattern::Pattern
+};
 
 #[cfg(feature = "colored_stdout_output")]
-use super::util::colored_logline::ColoredLogLine as LogLine;
+use std::io::IsTerminal;
 
-#[cfg(not(feature = "colored_stdout_output"))]
-use super::util::LogLine;
+#[cfg(feature = "colored_stdout_output")]
+use super::util::colored_logline::{ColorMode, ColorTheme, LazyColorMode};
+use super::util::{
+	layout::{self, Layout, LayoutStyle},
+	LineFields,
+	TimestampFormat,
+	TimestampFormatter
+};
 
+/// Writes records to the standard output, each line using a single `write_all`.
 pub struct StdoutTarget {
 	level: Level,
-	ignore_list: IgnoreList<'static>
+	ignore_list: IgnoreList<'static>,
+	pattern: Option<Pattern>,
+	fields: LineFields,
+	timestamps: TimestampFormatter,
+	#[cfg(feature = "colored_stdout_output")]
+	color_mode: LazyColorMode,
+	#[cfg(feature = "colored_stdout_output")]
+	theme: ColorTheme
 }
 impl StdoutTarget {
 	pub const fn new(level: Level, ignore_patterns: IgnoreListPatterns<'static>) -> Self {
 		StdoutTarget {
 			level,
-			ignore_list: IgnoreList::new(ignore_patterns)
+			ignore_list: IgnoreList::new(ignore_patterns),
+			pattern: None,
+			fields: LineFields::NONE,
+			timestamps: TimestampFormatter::new(TimestampFormat::DEFAULT),
+			#[cfg(feature = "colored_stdout_output")]
+			color_mode: LazyColorMode::new(ColorMode::Auto),
+			#[cfg(feature = "colored_stdout_output")]
+			theme: ColorTheme::DEFAULT
+		}
+	}
+
+	/// Sets the optional record fields included in lines, none by default.
+	pub const fn with_fields(mut self, fields: LineFields) -> Self {
+		self.fields = fields;
+		self
+	}
+
+	/// Sets how the timestamps are rendered, `TimestampFormat::DEFAULT` by default.
+	pub const fn with_timestamp_format(mut self, format: TimestampFormat) -> Self {
+		self.timestamps = TimestampFormatter::new(format);
+		self
+	}
+
+	/// Sets the line layout, the default `LogLine` layout is used otherwise.
+	///
+	/// Lines rendered using a pattern are never colored.
+	pub fn with_pattern(mut self, pattern: Pattern) -> Self {
+		self.pattern = Some(pattern);
+		self
+	}
+
+	/// Sets whether the output is colored, defaults to `ColorMode::Auto`.
+	#[cfg(feature = "colored_stdout_output")]
+	pub const fn with_color_mode(mut self, color_mode: ColorMode) -> Self {
+		self.color_mode = LazyColorMode::new(color_mode);
+		self
+	}
+
+	/// Sets the theme of colored output, defaults to `ColorTheme::DEFAULT`.
+	#[cfg(feature = "colored_stdout_output")]
+	pub const fn with_theme(mut self, theme: ColorTheme) -> Self {
+		self.theme = theme;
+		self
+	}
+
+	fn line_layout(&self, duration_since_start: Duration) -> Layout<'_> {
+		let mut layout = Layout {
+			style: LayoutStyle::Plain,
+			timestamp: self.timestamps.duration(duration_since_start),
+			timestamp_format: self.timestamps.format(),
+			fields: self.fields
+		};
+
+		if let Some(ref pattern) = self.pattern {
+			layout.style = LayoutStyle::Pattern(pattern);
+			return layout
+		}
+
+		#[cfg(feature = "colored_stdout_output")]
+		if self.color_mode.enabled(|| io::stdout().is_terminal()) {
+			layout.style = LayoutStyle::Colored(&self.theme);
 		}
+
+		layout
 	}
 }
 impl Default for StdoutTarget {
 	fn default() -> Self {
 		StdoutTarget {
 			level: log::Level::Trace,
-			ignore_list: Default::default()
+			ignore_list: Default::default(),
+			pattern: None,
+			fields: LineFields::NONE,
+			timestamps: TimestampFormatter::new(TimestampFormat::DEFAULT),
+			#[cfg(feature = "colored_stdout_output")]
+			color_mode: Default::default(),
+			#[cfg(feature = "colored_stdout_output")]
+			theme: Default::default()
 		}
 	}
 }
@@ -39,13 +125,27 @@ impl Target for StdoutTarget {
 		self.level
 	}
 
-	fn ignore(&self, record: &Record) -> bool {
-		self.ignore_list.ignore(record)
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		metadata.level() <= self.level && !self.ignore_list.ignore_target(metadata.target())
 	}
 
 	fn write(&self, duration_since_start: Duration, record: &Record) -> io::Result<()> {
-		let log_line = LogLine::new(duration_since_start.into(), record);
-		writeln!(&mut io::stdout(), "{}", log_line)
+		layout::write_with_layout(self.line_layout(duration_since_start), record, |line| {
+			self.write_rendered(duration_since_start, record, line)
+		})
+	}
+
+	fn layout(&self, duration_since_start: Duration) -> Option<Layout<'_>> {
+		Some(self.line_layout(duration_since_start))
+	}
+
+	fn write_rendered(
+		&self,
+		_duration_since_start: Duration,
+		_record: &Record,
+		line: &[u8]
+	) -> io::Result<()> {
+		io::stdout().write_all(line)
 	}
 
 	fn flush(&self) -> io::Result<()> {
@@ -0,0 +1,13 @@
+//! Helpers shared by the tests of the file targets.
+
+use std::{fs, path::PathBuf};
+
+/// Returns an empty directory for the test `name`.
+pub fn test_dir(name: &str) -> PathBuf {
+	let dir =
+		std::env::temp_dir().join(format!("edwardium-logger-{}-{}", name, std::process::id()));
+	let _ = fs::remove_dir_all(&dir);
+	fs::create_dir_all(&dir).unwrap();
+
+	dir
+}
@@ -1,9 +1,11 @@
 use std::{
+	cell::RefCell,
 	fmt::{Error, Write},
-	sync::Mutex,
 	time::Duration
 };
 
+use critical_section::Mutex;
+
 use embedded_serial::{
 	ImmutBlockingTx,
 	ImmutBlockingTxWithTimeout,
@@ -16,6 +18,13 @@ use log::{Level, Record};
 
 use crate::target::Target;
 
+use super::util::{LineFields, LogLine, TimestampFormat, TimestampFormatter};
+
+#[cfg(feature = "binary_logline")]
+use super::util::binary::{BinaryEncoder, ByteSink};
+#[cfg(feature = "colored_logline")]
+use super::util::colored_logline::{ColorTheme, ColoredLogLine};
+
 /// Marker trait to avoid repetition.
 pub trait WritableTx: Write {
 	/// Type that will be wrapped.
@@ -28,6 +37,9 @@ pub trait WritableTx: Write {
 	fn new(sink: Self::Type, data: Self::Data) -> Self
 	where
 		Self: Sized;
+
+	/// Writes raw bytes into the wrapped sink.
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error>;
 }
 
 macro_rules! impl_writable_tx_wrap {
@@ -36,17 +48,19 @@ macro_rules! impl_writable_tx_wrap {
 			sink,
 			data: $data_type: ty
 		}
-		impl Write {
+		impl WritableTx {
 			$( $write_impl: tt )+
 		}
 	) => {
-		struct $wrap_name<T: $trait_name> {
+		pub struct $wrap_name<T: $trait_name> {
 			sink: T,
 			#[allow(dead_code)]
 			data: $data_type
 		}
 		impl<T: $trait_name> Write for $wrap_name<T> {
-			$( $write_impl )+
+			fn write_str(&mut self, s: &str) -> Result<(), Error> {
+				self.write_bytes(s.as_bytes())
+			}
 		}
 		impl<T: $trait_name> WritableTx for $wrap_name<T> {
 			type Type = T;
@@ -58,6 +72,8 @@ macro_rules! impl_writable_tx_wrap {
 					data
 				}
 			}
+
+			$( $write_impl )+
 		}
 	}
 }
@@ -67,9 +83,9 @@ impl_writable_tx_wrap! {
 		sink,
 		data: ()
 	}
-	impl Write {
-		fn write_str(&mut self, s: &str) -> Result<(), Error> {
-			match self.sink.puts(s.as_bytes()) {
+	impl WritableTx {
+		fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+			match self.sink.puts(bytes) {
 				Ok(()) => Ok(()),
 				Err((_len, _err)) => Err(std::fmt::Error)
 			}
@@ -81,9 +97,9 @@ impl_writable_tx_wrap! {
 		sink,
 		data: T::Timeout
 	}
-	impl Write {
-		fn write_str(&mut self, s: &str) -> Result<(), Error> {
-			match self.sink.puts_wait(s.as_bytes(), &self.data) {
+	impl WritableTx {
+		fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+			match self.sink.puts_wait(bytes, &self.data) {
 				Ok(_len) => Ok(()),
 				Err((_len, _err)) => Err(std::fmt::Error)
 			}
@@ -118,13 +134,13 @@ impl_writable_tx_wrap! {
 //			Ok(())
 //		}
 //	}
-	impl Write {
-		fn write_str(&mut self, s: &str) -> Result<(), Error> {
+	impl WritableTx {
+		fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
 			let mut start = 0;
 
 			for _ in 0 .. self.data + 1 {
-				match self.sink.puts_try(&s.as_bytes()[start ..]) {
-					Ok(len) if start + len == s.as_bytes().len() => {
+				match self.sink.puts_try(&bytes[start ..]) {
+					Ok(len) if start + len == bytes.len() => {
 						return Ok(())
 					}
 					Ok(len) => {
@@ -145,9 +161,9 @@ impl_writable_tx_wrap! {
 		sink,
 		data: ()
 	}
-	impl Write {
-		fn write_str(&mut self, s: &str) -> Result<(), Error> {
-			match self.sink.puts(s.as_bytes()) {
+	impl WritableTx {
+		fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+			match self.sink.puts(bytes) {
 				Ok(()) => Ok(()),
 				Err((_len, _err)) => Err(std::fmt::Error)
 			}
@@ -159,9 +175,9 @@ impl_writable_tx_wrap! {
 		sink,
 		data: T::Timeout
 	}
-	impl Write {
-		fn write_str(&mut self, s: &str) -> Result<(), Error> {
-			match self.sink.puts_wait(s.as_bytes(), &self.data) {
+	impl WritableTx {
+		fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+			match self.sink.puts_wait(bytes, &self.data) {
 				Ok(_len) => Ok(()),
 				Err((_len, _err)) => Err(std::fmt::Error)
 			}
@@ -196,13 +212,13 @@ impl_writable_tx_wrap! {
 //			Ok(())
 //		}
 //	}
-	impl Write {
-		fn write_str(&mut self, s: &str) -> Result<(), Error> {
+	impl WritableTx {
+		fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
 			let mut start = 0;
 
 			for _ in 0 .. self.data + 1 {
-				match self.sink.puts_try(&s.as_bytes()[start ..]) {
-					Ok(len) if start + len == s.as_bytes().len() => {
+				match self.sink.puts_try(&bytes[start ..]) {
+					Ok(len) if start + len == bytes.len() => {
 						return Ok(())
 					}
 					Ok(len) => {
@@ -218,17 +234,50 @@ impl_writable_tx_wrap! {
 	}
 }
 
+/// Uart target that writes the standard text lines.
+///
+/// The sink is locked using a critical section, so interrupts may be disabled while a line is being written.
+///
+/// The final binary has to provide a [`critical-section`](https://docs.rs/critical-section) implementation,
+/// even with `std`, where the `std` feature of `critical-section` can be enabled. Otherwise linking fails.
 pub struct UartTarget<T: WritableTx> {
 	level: Level,
-	sink: Mutex<T>
+	sink: Mutex<RefCell<T>>,
+	fields: LineFields,
+	timestamps: TimestampFormatter,
+	#[cfg(feature = "colored_logline")]
+	theme: Option<ColorTheme>
 }
 impl<T: WritableTx> UartTarget<T> {
 	pub fn new(level: Level, sink: T::Type, config: T::Data) -> Self {
 		UartTarget {
 			level,
-			sink: Mutex::new(T::new(sink, config))
+			sink: Mutex::new(RefCell::new(T::new(sink, config))),
+			fields: LineFields::NONE,
+			timestamps: TimestampFormatter::new(TimestampFormat::DEFAULT),
+			#[cfg(feature = "colored_logline")]
+			theme: None
 		}
 	}
+
+	/// Sets the optional record fields included in lines, none by default.
+	pub fn with_fields(mut self, fields: LineFields) -> Self {
+		self.fields = fields;
+		self
+	}
+
+	/// Sets how the timestamps are rendered, `TimestampFormat::DEFAULT` by default.
+	pub fn with_timestamp_format(mut self, format: TimestampFormat) -> Self {
+		self.timestamps = TimestampFormatter::new(format);
+		self
+	}
+
+	/// Enables colored output for serial terminals that understand ANSI escape sequences.
+	#[cfg(feature = "colored_logline")]
+	pub fn with_theme(mut self, theme: ColorTheme) -> Self {
+		self.theme = Some(theme);
+		self
+	}
 }
 impl<T: WritableTx> Target for UartTarget<T> {
 	type Error = Error;
@@ -240,10 +289,22 @@ impl<T: WritableTx> Target for UartTarget<T> {
 	}
 
 	fn write(&self, duration_since_start: Duration, record: &Record) -> Result<(), Self::Error> {
-		let log_line = super::util::LogLine::new(duration_since_start.into(), record);
+		critical_section::with(|cs| {
+			let mut sink = self.sink.borrow_ref_mut(cs);
 
-		let mut lock = self.sink.lock().unwrap();
-		writeln!(&mut lock, "{}", log_line)
+			#[cfg(feature = "colored_logline")]
+			if let Some(ref theme) = self.theme {
+				let log_line =
+					ColoredLogLine::new(self.timestamps.timestamp(duration_since_start), record)
+						.with_fields(self.fields, record)
+						.with_theme(theme);
+				return writeln!(&mut *sink, "{}", log_line)
+			}
+
+			let log_line = LogLine::new(self.timestamps.timestamp(duration_since_start), record)
+				.with_fields(self.fields, record);
+			writeln!(&mut *sink, "{}", log_line)
+		})
 	}
 
 	fn flush(&self) -> Result<(), Self::Error> {
@@ -251,3 +312,59 @@ impl<T: WritableTx> Target for UartTarget<T> {
 		Ok(())
 	}
 }
+
+/// Adapts a `WritableTx` to the binary encoder byte sink.
+#[cfg(feature = "binary_logline")]
+struct TxByteSink<'a, T: WritableTx>(&'a mut T);
+#[cfg(feature = "binary_logline")]
+impl<'a, T: WritableTx> ByteSink for TxByteSink<'a, T> {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+		self.0.write_bytes(bytes)
+	}
+}
+
+/// Uart target that sends records using the compact [binary encoding](../util/binary/index.html).
+///
+/// Up to `N` distinct record targets are interned.
+///
+/// Locks its sink like [`UartTarget`](struct.UartTarget.html) and needs a `critical-section` implementation as well.
+#[cfg(feature = "binary_logline")]
+pub struct UartBinaryTarget<T: WritableTx, const N: usize = 32> {
+	level: Level,
+	sink: Mutex<RefCell<(T, BinaryEncoder<N>)>>
+}
+#[cfg(feature = "binary_logline")]
+impl<T: WritableTx, const N: usize> UartBinaryTarget<T, N> {
+	pub fn new(level: Level, sink: T::Type, config: T::Data) -> Self {
+		UartBinaryTarget {
+			level,
+			sink: Mutex::new(RefCell::new((T::new(sink, config), BinaryEncoder::new())))
+		}
+	}
+
+	/// Makes the encoder resend target definitions, for example after the receiver was reconnected.
+	pub fn reset(&self) {
+		critical_section::with(|cs| self.sink.borrow_ref_mut(cs).1.reset());
+	}
+}
+#[cfg(feature = "binary_logline")]
+impl<T: WritableTx, const N: usize> Target for UartBinaryTarget<T, N> {
+	type Error = Error;
+
+	fn level(&self) -> Level {
+		self.level
+	}
+
+	fn write(&self, duration_since_start: Duration, record: &Record) -> Result<(), Self::Error> {
+		critical_section::with(|cs| {
+			let mut lock = self.sink.borrow_ref_mut(cs);
+			let (ref mut sink, ref mut encoder) = *lock;
+
+			encoder.encode(&mut TxByteSink(sink), duration_since_start, record)
+		})
+	}
+
+	fn flush(&self) -> Result<(), Self::Error> {
+		Ok(())
+	}
+}
@@ -1,40 +1,296 @@
 use std::{
-	fmt::{Arguments, Display, Error, Formatter},
+	fmt::{Arguments, Display, Error, Formatter, Write},
 	time::Duration
 };
 
 use log::{Level, Record};
 
+#[cfg(feature = "colored_logline")]
+pub mod ansi;
+#[cfg(feature = "binary_logline")]
+pub mod binary;
+#[cfg(feature = "std")]
+pub mod layout;
+#[cfg(feature = "std")]
+pub mod pattern;
+
+/// Precision of the fractional part of a timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+	Millis,
+	Micros,
+	Nanos
+}
+
+/// Largest unit a timestamp is broken down into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LargestUnit {
+	/// `+mmm:ss.fff`
+	Minutes,
+	/// `+hh:mm:ss.fff`
+	Hours,
+	/// `+Dd hh:mm:ss.fff`
+	Days
+}
+
+/// How a `Timestamp` is rendered.
+///
+/// The default is the `+mmm:ss.mmmm` format of earlier versions. With `Minutes` and `Millis` the milliseconds
+/// are padded to four digits as they always were, so that existing tooling keeps working.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampFormat {
+	pub precision: Precision,
+	pub largest_unit: LargestUnit,
+	/// Render the time since the previous record instead of the time since start.
+	///
+	/// Only applies to timestamps computed by a [`TimestampFormatter`](struct.TimestampFormatter.html)
+	/// and requires 64-bit atomics, on other platforms the time since start is rendered.
+	pub delta: bool
+}
+impl TimestampFormat {
+	pub const DEFAULT: TimestampFormat = TimestampFormat {
+		precision: Precision::Millis,
+		largest_unit: LargestUnit::Minutes,
+		delta: false
+	};
+
+	pub const fn with_precision(mut self, precision: Precision) -> Self {
+		self.precision = precision;
+		self
+	}
+
+	pub const fn with_largest_unit(mut self, largest_unit: LargestUnit) -> Self {
+		self.largest_unit = largest_unit;
+		self
+	}
+
+	pub const fn with_delta(mut self, delta: bool) -> Self {
+		self.delta = delta;
+		self
+	}
+}
+impl Default for TimestampFormat {
+	fn default() -> Self {
+		TimestampFormat::DEFAULT
+	}
+}
+
+/// Turns durations since start into timestamps according to a `TimestampFormat`.
+///
+/// In delta mode it remembers the duration of the previous record.
+#[derive(Debug)]
+pub struct TimestampFormatter {
+	format: TimestampFormat,
+	#[cfg(target_has_atomic = "64")]
+	previous_nanos: core::sync::atomic::AtomicU64
+}
+impl TimestampFormatter {
+	pub const fn new(format: TimestampFormat) -> Self {
+		TimestampFormatter {
+			format,
+			#[cfg(target_has_atomic = "64")]
+			previous_nanos: core::sync::atomic::AtomicU64::new(0)
+		}
+	}
+
+	pub const fn format(&self) -> TimestampFormat {
+		self.format
+	}
+
+	/// Returns the duration rendered as the timestamp, the time since the previous call in delta mode.
+	pub fn duration(&self, duration_since_start: Duration) -> Duration {
+		#[cfg(target_has_atomic = "64")]
+		if self.format.delta {
+			let nanos = duration_since_start.as_nanos() as u64;
+			let previous = self
+				.previous_nanos
+				.swap(nanos, core::sync::atomic::Ordering::Relaxed);
+
+			return Duration::from_nanos(nanos.saturating_sub(previous))
+		}
+
+		duration_since_start
+	}
+
+	pub fn timestamp(&self, duration_since_start: Duration) -> Timestamp {
+		Timestamp::from(self.duration(duration_since_start)).with_format(self.format)
+	}
+}
+impl Default for TimestampFormatter {
+	fn default() -> Self {
+		TimestampFormatter::new(TimestampFormat::DEFAULT)
+	}
+}
+
 pub struct Timestamp {
-	minutes: u64,
-	seconds: u64,
-	millis: u32
+	duration: Duration,
+	format: TimestampFormat
 }
 impl Timestamp {
 	pub const fn new(minutes: u64, seconds: u64, millis: u32) -> Self {
 		Timestamp {
-			minutes,
-			seconds,
-			millis
+			duration: Duration::new(minutes * 60 + seconds, millis * 1_000_000),
+			format: TimestampFormat::DEFAULT
 		}
 	}
+
+	pub const fn with_format(mut self, format: TimestampFormat) -> Self {
+		self.format = format;
+		self
+	}
 }
 impl From<Duration> for Timestamp {
 	fn from(duration: Duration) -> Self {
-		let minutes = duration.as_secs() / 60;
-		let seconds = duration.as_secs() % 60;
-		let millis = duration.subsec_millis();
-
-		Timestamp::new(minutes, seconds, millis)
+		Timestamp {
+			duration,
+			format: TimestampFormat::DEFAULT
+		}
 	}
 }
 impl Display for Timestamp {
 	fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-		write!(
-			f,
-			"+{:0>3}:{:0>2}.{:0>4}",
-			self.minutes, self.seconds, self.millis
-		)
+		let secs = self.duration.as_secs();
+		let nanos = self.duration.subsec_nanos();
+
+		match self.format.largest_unit {
+			LargestUnit::Minutes => write!(f, "+{:0>3}:{:0>2}", secs / 60, secs % 60)?,
+			LargestUnit::Hours => write!(
+				f,
+				"+{:0>2}:{:0>2}:{:0>2}",
+				secs / 3600,
+				secs / 60 % 60,
+				secs % 60
+			)?,
+			LargestUnit::Days => write!(
+				f,
+				"+{}d {:0>2}:{:0>2}:{:0>2}",
+				secs / 86400,
+				secs / 3600 % 24,
+				secs / 60 % 60,
+				secs % 60
+			)?
+		}
+
+		match (self.format.precision, self.format.largest_unit) {
+			(Precision::Millis, LargestUnit::Minutes) => write!(f, ".{:0>4}", nanos / 1_000_000),
+			(Precision::Millis, _) => write!(f, ".{:0>3}", nanos / 1_000_000),
+			(Precision::Micros, _) => write!(f, ".{:0>6}", nanos / 1_000),
+			(Precision::Nanos, _) => write!(f, ".{:0>9}", nanos)
+		}
+	}
+}
+
+/// Optional record information included in log lines.
+///
+/// Each enabled field is rendered as a `[key=value]` segment after the level.
+/// A `\` or `]` in a value is escaped by a preceding `\`, so that a thread named `a]b` is rendered as `[thread=a\]b]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LineFields {
+	/// Source file and line, `at=file:line`.
+	pub location: bool,
+	/// Module path, `module=path`.
+	pub module_path: bool,
+	/// Name of the logging thread, `thread=name`. Unnamed threads are rendered using their id.
+	pub thread_name: bool,
+	/// Id of the logging thread, `thread_id=ThreadId(n)`.
+	pub thread_id: bool,
+	/// Process id, `pid=n`.
+	pub process_id: bool
+}
+impl LineFields {
+	pub const ALL: LineFields = LineFields {
+		location: true,
+		module_path: true,
+		thread_name: true,
+		thread_id: true,
+		process_id: true
+	};
+	pub const NONE: LineFields = LineFields {
+		location: false,
+		module_path: false,
+		thread_name: false,
+		thread_id: false,
+		process_id: false
+	};
+}
+
+/// Field value with `\` and `]` escaped by a preceding `\`.
+struct Escaped<'a>(&'a dyn Display);
+impl Display for Escaped<'_> {
+	fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+		struct Escaper<'a, 'f>(&'a mut Formatter<'f>);
+		impl Write for Escaper<'_, '_> {
+			fn write_str(&mut self, mut s: &str) -> Result<(), Error> {
+				while let Some(index) = s.find(['\\', ']']) {
+					self.0.write_str(&s[.. index])?;
+					self.0.write_char('\\')?;
+					self.0.write_str(&s[index ..= index])?;
+					s = &s[index + 1 ..];
+				}
+				self.0.write_str(s)
+			}
+		}
+
+		write!(Escaper(f), "{}", self.0)
+	}
+}
+
+/// Source of the `[key=value]` segments of a log line.
+#[derive(Clone, Copy)]
+enum Fields<'r> {
+	None,
+	Record(LineFields, &'r Record<'r>),
+	#[cfg(feature = "std")]
+	Parsed(&'r [(String, String)])
+}
+impl<'r> Fields<'r> {
+	/// Calls `cb` with each segment, the values are escaped.
+	///
+	/// Thread and process fields are only available with the `std` feature.
+	fn for_each(
+		&self,
+		mut cb: impl FnMut(&str, &dyn Display) -> Result<(), Error>
+	) -> Result<(), Error> {
+		let mut cb = |key: &str, value: &dyn Display| cb(key, &Escaped(value));
+
+		match *self {
+			Fields::None => Ok(()),
+			Fields::Record(fields, record) => {
+				if fields.location {
+					match (record.file(), record.line()) {
+						(Some(file), Some(line)) => cb("at", &format_args!("{}:{}", file, line))?,
+						(Some(file), None) => cb("at", &file)?,
+						_ => cb("at", &"?")?
+					}
+				}
+				if fields.module_path {
+					cb("module", &record.module_path().unwrap_or("?"))?;
+				}
+
+				#[cfg(feature = "std")]
+				{
+					if fields.thread_name || fields.thread_id {
+						let thread = std::thread::current();
+						if fields.thread_name {
+							match thread.name() {
+								Some(name) => cb("thread", &name)?,
+								None => cb("thread", &format_args!("{:?}", thread.id()))?
+							}
+						}
+						if fields.thread_id {
+							cb("thread_id", &format_args!("{:?}", thread.id()))?;
+						}
+					}
+					if fields.process_id {
+						cb("pid", &std::process::id())?;
+					}
+				}
+
+				Ok(())
+			}
+			#[cfg(feature = "std")]
+			Fields::Parsed(fields) => fields.iter().try_for_each(|(key, value)| cb(key, value))
+		}
 	}
 }
 
@@ -42,7 +298,8 @@ pub struct LogLine<'r> {
 	timestamp: Timestamp,
 	level: Level,
 	target: &'r str,
-	args: &'r Arguments<'r>
+	args: &'r Arguments<'r>,
+	fields: Fields<'r>
 }
 impl<'r> LogLine<'r> {
 	pub fn new(timestamp: Timestamp, record: &'r Record<'r>) -> Self {
@@ -50,20 +307,50 @@ impl<'r> LogLine<'r> {
 			timestamp,
 			level: record.level(),
 			target: record.target(),
-			args: record.args()
+			args: record.args(),
+			fields: Fields::None
 		}
 	}
+
+	/// Creates a log line from already extracted record parts.
+	pub fn from_parts(
+		timestamp: Timestamp,
+		level: Level,
+		target: &'r str,
+		args: &'r Arguments<'r>
+	) -> Self {
+		LogLine {
+			timestamp,
+			level,
+			target,
+			args,
+			fields: Fields::None
+		}
+	}
+
+	/// Includes the selected fields of `record`.
+	pub fn with_fields(mut self, fields: LineFields, record: &'r Record<'r>) -> Self {
+		self.fields = Fields::Record(fields, record);
+		self
+	}
+
+	/// Includes already rendered `key=value` fields, for example ones read by the [`parse`](../../parse/index.html) module.
+	#[cfg(feature = "std")]
+	pub fn with_parsed_fields(mut self, fields: &'r [(String, String)]) -> Self {
+		self.fields = Fields::Parsed(fields);
+		self
+	}
 }
 impl<'r> Display for LogLine<'r> {
 	fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-		write!(
-			f,
-			"[{}][{}] ({}) {}",
-			self.timestamp, self.level, self.target, self.args
-		)
+		write!(f, "[{}][{}]", self.timestamp, self.level)?;
+		self.fields
+			.for_each(|key, value| write!(f, "[{}={}]", key, value))?;
+		write!(f, " ({}) {}", self.target, self.args)
 	}
 }
 
+#[cfg(feature = "std")]
 pub mod ignore_list {
 	use log::Record;
 	use std::borrow::Cow;
@@ -82,28 +369,189 @@ pub mod ignore_list {
 		}
 
 		pub fn ignore(&self, record: &Record) -> bool {
-			self.patterns
-				.iter()
-				.any(|p| record.target().contains(p.as_ref()))
+			self.ignore_target(record.target())
+		}
+
+		/// Whether records with given target are ignored.
+		pub fn ignore_target(&self, target: &str) -> bool {
+			self.patterns.iter().any(|p| target.contains(p.as_ref()))
 		}
 	}
 }
 
 #[cfg(feature = "colored_logline")]
 pub mod colored_logline {
-	use std::fmt::{Arguments, Display, Error, Formatter};
+	use std::{
+		fmt::{Arguments, Display, Error, Formatter},
+		sync::atomic::{AtomicU8, Ordering}
+	};
 
 	use log::{Level, Record};
 
-	use termion::color::{self, Fg};
+	pub use super::ansi::{Color, Painted, Style};
+	use super::{Fields, LineFields, Timestamp};
 
-	use super::Timestamp;
+	/// Whether a target emits colored output.
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+	pub enum ColorMode {
+		/// Colors are emitted if the output is a terminal.
+		///
+		/// With the `std` feature, `NO_COLOR` set to a non-empty value disables colors and `CLICOLOR_FORCE` set
+		/// to a value other than `0` enables them regardless of the output.
+		#[default]
+		Auto,
+		Always,
+		Never
+	}
+	impl ColorMode {
+		/// Resolves the mode for an output, `is_terminal` is only called in `Auto` mode.
+		pub fn enabled(self, is_terminal: impl FnOnce() -> bool) -> bool {
+			#[cfg(feature = "std")]
+			fn env_set(name: &str, disabled: &str) -> bool {
+				std::env::var_os(name).is_some_and(|value| value != disabled)
+			}
+			#[cfg(not(feature = "std"))]
+			fn env_set(_name: &str, _disabled: &str) -> bool {
+				false
+			}
+
+			match self {
+				ColorMode::Always => true,
+				ColorMode::Never => false,
+				ColorMode::Auto if env_set("NO_COLOR", "") => false,
+				ColorMode::Auto if env_set("CLICOLOR_FORCE", "0") => true,
+				ColorMode::Auto => is_terminal()
+			}
+		}
+	}
+
+	/// `ColorMode` which remembers its resolution so that the environment is only inspected once.
+	#[derive(Debug)]
+	pub struct LazyColorMode {
+		mode: ColorMode,
+		/// 0 - unresolved, 1 - enabled, 2 - disabled
+		resolved: AtomicU8
+	}
+	impl LazyColorMode {
+		pub const fn new(mode: ColorMode) -> Self {
+			LazyColorMode {
+				mode,
+				resolved: AtomicU8::new(0)
+			}
+		}
+
+		pub fn mode(&self) -> ColorMode {
+			self.mode
+		}
+
+		/// Returns whether colors are enabled, resolving the mode on the first call.
+		pub fn enabled(&self, is_terminal: impl FnOnce() -> bool) -> bool {
+			match self.resolved.load(Ordering::Relaxed) {
+				1 => true,
+				2 => false,
+				_ => {
+					let enabled = self.mode.enabled(is_terminal);
+					self.resolved
+						.store(if enabled { 1 } else { 2 }, Ordering::Relaxed);
+
+					enabled
+				}
+			}
+		}
+	}
+	impl Default for LazyColorMode {
+		fn default() -> Self {
+			LazyColorMode::new(ColorMode::default())
+		}
+	}
+
+	/// Styles of the parts of a `ColoredLogLine`.
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub struct ColorTheme {
+		pub timestamp: Style,
+		/// Style of the optional `key=value` fields.
+		pub fields: Style,
+		pub target: Style,
+		pub message: Style,
+		pub error: Style,
+		pub warn: Style,
+		pub info: Style,
+		pub debug: Style,
+		pub trace: Style
+	}
+	impl ColorTheme {
+		/// Theme for dark terminals.
+		pub const DEFAULT: ColorTheme = ColorTheme {
+			timestamp: Style::fg(Color::Yellow),
+			fields: Style::fg(Color::LightBlack),
+			target: Style::fg(Color::Cyan),
+			message: Style::PLAIN,
+			error: Style::fg(Color::Red),
+			warn: Style::fg(Color::Yellow),
+			info: Style::fg(Color::Green),
+			debug: Style::fg(Color::Blue),
+			trace: Style::fg(Color::Magenta)
+		};
+		/// Theme that emphasizes errors and warnings with a background and dims everything else.
+		pub const HIGH_CONTRAST: ColorTheme = ColorTheme {
+			timestamp: Style::PLAIN.dim(),
+			fields: Style::PLAIN.dim(),
+			target: Style::PLAIN.bold(),
+			message: Style::PLAIN,
+			error: Style::fg(Color::LightWhite).on(Color::Red).bold(),
+			warn: Style::fg(Color::Black).on(Color::Yellow),
+			info: Style::fg(Color::LightGreen),
+			debug: Style::PLAIN.dim(),
+			trace: Style::PLAIN.dim()
+		};
+		/// Theme for light terminals, avoids yellow and light colors which are hard to read on white background.
+		pub const LIGHT: ColorTheme = ColorTheme {
+			timestamp: Style::fg(Color::Ansi256(242)),
+			fields: Style::fg(Color::Ansi256(245)),
+			target: Style::fg(Color::Ansi256(25)),
+			message: Style::PLAIN,
+			error: Style::fg(Color::Ansi256(160)).bold(),
+			warn: Style::fg(Color::Ansi256(130)).bold(),
+			info: Style::fg(Color::Ansi256(28)),
+			debug: Style::fg(Color::Ansi256(19)),
+			trace: Style::fg(Color::Ansi256(90))
+		};
+		/// Truecolor theme based on the Solarized palette.
+		pub const SOLARIZED: ColorTheme = ColorTheme {
+			timestamp: Style::fg(Color::Rgb(0x58, 0x6e, 0x75)),
+			fields: Style::fg(Color::Rgb(0x93, 0xa1, 0xa1)),
+			target: Style::fg(Color::Rgb(0x26, 0x8b, 0xd2)),
+			message: Style::PLAIN,
+			error: Style::fg(Color::Rgb(0xdc, 0x32, 0x2f)).bold(),
+			warn: Style::fg(Color::Rgb(0xcb, 0x4b, 0x16)),
+			info: Style::fg(Color::Rgb(0x85, 0x99, 0x00)),
+			debug: Style::fg(Color::Rgb(0x2a, 0xa1, 0x98)),
+			trace: Style::fg(Color::Rgb(0x6c, 0x71, 0xc4))
+		};
+
+		pub const fn level(&self, level: Level) -> &Style {
+			match level {
+				Level::Error => &self.error,
+				Level::Warn => &self.warn,
+				Level::Info => &self.info,
+				Level::Debug => &self.debug,
+				Level::Trace => &self.trace
+			}
+		}
+	}
+	impl Default for ColorTheme {
+		fn default() -> Self {
+			ColorTheme::DEFAULT
+		}
+	}
 
 	pub struct ColoredLogLine<'r> {
 		timestamp: Timestamp,
 		level: Level,
 		target: &'r str,
-		args: &'r Arguments<'r>
+		args: &'r Arguments<'r>,
+		fields: Fields<'r>,
+		theme: &'r ColorTheme
 	}
 	impl<'r> ColoredLogLine<'r> {
 		pub fn new(timestamp: Timestamp, record: &'r Record<'r>) -> Self {
@@ -111,43 +559,68 @@ pub mod colored_logline {
 				timestamp,
 				level: record.level(),
 				target: record.target(),
-				args: record.args()
+				args: record.args(),
+				fields: Fields::None,
+				theme: &ColorTheme::DEFAULT
 			}
 		}
-	}
-	impl<'r> Display for ColoredLogLine<'r> {
-		fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-			#[derive(Debug)]
-			struct ApiDesignIsHard(Level);
-			impl termion::color::Color for ApiDesignIsHard {
-				fn write_fg(&self, f: &mut Formatter) -> Result<(), Error> {
-					match self.0 {
-						Level::Error => color::Red.write_fg(f),
-						Level::Warn => color::Yellow.write_fg(f),
-						Level::Info => color::Green.write_fg(f),
-						Level::Debug => color::Blue.write_fg(f),
-						Level::Trace => color::Magenta.write_fg(f)
-					}
-				}
 
-				fn write_bg(&self, _: &mut Formatter) -> Result<(), Error> {
-					unimplemented!()
-				}
+		/// Creates a log line from already extracted record parts.
+		pub fn from_parts(
+			timestamp: Timestamp,
+			level: Level,
+			target: &'r str,
+			args: &'r Arguments<'r>
+		) -> Self {
+			ColoredLogLine {
+				timestamp,
+				level,
+				target,
+				args,
+				fields: Fields::None,
+				theme: &ColorTheme::DEFAULT
 			}
+		}
+
+		/// Includes the selected fields of `record`.
+		pub fn with_fields(mut self, fields: LineFields, record: &'r Record<'r>) -> Self {
+			self.fields = Fields::Record(fields, record);
+			self
+		}
 
+		/// Includes already rendered `key=value` fields, for example ones read by the [`parse`](../../../parse/index.html) module.
+		#[cfg(feature = "std")]
+		pub fn with_parsed_fields(mut self, fields: &'r [(String, String)]) -> Self {
+			self.fields = Fields::Parsed(fields);
+			self
+		}
+
+		/// Sets the theme, `ColorTheme::DEFAULT` is used otherwise.
+		pub fn with_theme(mut self, theme: &'r ColorTheme) -> Self {
+			self.theme = theme;
+			self
+		}
+	}
+	impl<'r> Display for ColoredLogLine<'r> {
+		fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+			write!(
+				f,
+				"[{}][{}]",
+				self.theme.timestamp.paint(&self.timestamp),
+				self.theme.level(self.level).paint(self.level)
+			)?;
+			self.fields.for_each(|key, value| {
+				write!(
+					f,
+					"[{}]",
+					self.theme.fields.paint(format_args!("{}={}", key, value))
+				)
+			})?;
 			write!(
 				f,
-				"[{}{}{}][{}{}{}] ({}{}{}) {}",
-				Fg(color::Yellow),
-				self.timestamp,
-				Fg(color::Reset),
-				Fg(ApiDesignIsHard(self.level)),
-				self.level,
-				Fg(color::Reset),
-				Fg(color::Cyan),
-				self.target,
-				Fg(color::Reset),
-				self.args
+				" ({}) {}",
+				self.theme.target.paint(self.target),
+				self.theme.message.paint(self.args)
 			)
 		}
 	}
@@ -0,0 +1,152 @@
+//! Minimal ANSI escape sequence rendering.
+//!
+//! Only writes SGR (Select Graphic Rendition) sequences through `fmt::Formatter`, so it works in `no_std`
+//! and with any output the log line is written to, including serial terminals.
+
+use std::fmt::{Display, Error, Formatter};
+
+/// Resets all attributes.
+pub const RESET: &str = "\x1b[0m";
+
+/// Terminal color.
+///
+/// `Ansi256` is a color from the 256-color palette and `Rgb` is a truecolor, not all terminals support those.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+	Black,
+	Red,
+	Green,
+	Yellow,
+	Blue,
+	Magenta,
+	Cyan,
+	White,
+	LightBlack,
+	LightRed,
+	LightGreen,
+	LightYellow,
+	LightBlue,
+	LightMagenta,
+	LightCyan,
+	LightWhite,
+	Ansi256(u8),
+	Rgb(u8, u8, u8)
+}
+impl Color {
+	/// Writes the SGR parameters of this color, `base` is 30 for foreground and 40 for background.
+	fn write_params(&self, f: &mut Formatter, base: u8) -> Result<(), Error> {
+		let code = match *self {
+			Color::Black => base,
+			Color::Red => base + 1,
+			Color::Green => base + 2,
+			Color::Yellow => base + 3,
+			Color::Blue => base + 4,
+			Color::Magenta => base + 5,
+			Color::Cyan => base + 6,
+			Color::White => base + 7,
+			Color::LightBlack => base + 60,
+			Color::LightRed => base + 61,
+			Color::LightGreen => base + 62,
+			Color::LightYellow => base + 63,
+			Color::LightBlue => base + 64,
+			Color::LightMagenta => base + 65,
+			Color::LightCyan => base + 66,
+			Color::LightWhite => base + 67,
+			Color::Ansi256(value) => return write!(f, "{};5;{}", base + 8, value),
+			Color::Rgb(r, g, b) => return write!(f, "{};2;{};{};{}", base + 8, r, g, b)
+		};
+
+		write!(f, "{}", code)
+	}
+}
+
+/// Text style made of foreground and background colors and bold or dim intensity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+	pub fg: Option<Color>,
+	pub bg: Option<Color>,
+	pub bold: bool,
+	pub dim: bool
+}
+impl Style {
+	pub const PLAIN: Style = Style {
+		fg: None,
+		bg: None,
+		bold: false,
+		dim: false
+	};
+
+	pub const fn fg(color: Color) -> Self {
+		Style {
+			fg: Some(color),
+			..Style::PLAIN
+		}
+	}
+
+	/// Sets the background color.
+	pub const fn on(mut self, color: Color) -> Self {
+		self.bg = Some(color);
+		self
+	}
+
+	pub const fn bold(mut self) -> Self {
+		self.bold = true;
+		self
+	}
+
+	pub const fn dim(mut self) -> Self {
+		self.dim = true;
+		self
+	}
+
+	/// Returns a `Display` wrapper rendering `value` in this style.
+	pub fn paint<T: Display>(&self, value: T) -> Painted<'_, T> {
+		Painted { style: self, value }
+	}
+
+	/// Writes the escape sequence that switches to this style.
+	pub fn write_prefix(&self, f: &mut Formatter) -> Result<(), Error> {
+		if *self == Style::PLAIN {
+			return Ok(())
+		}
+
+		f.write_str("\x1b[")?;
+
+		let mut separator = "";
+		if self.bold {
+			f.write_str("1")?;
+			separator = ";";
+		}
+		if self.dim {
+			write!(f, "{}2", separator)?;
+			separator = ";";
+		}
+		if let Some(fg) = self.fg {
+			f.write_str(separator)?;
+			fg.write_params(f, 30)?;
+			separator = ";";
+		}
+		if let Some(bg) = self.bg {
+			f.write_str(separator)?;
+			bg.write_params(f, 40)?;
+		}
+
+		f.write_str("m")
+	}
+}
+
+pub struct Painted<'s, T: Display> {
+	style: &'s Style,
+	value: T
+}
+impl<'s, T: Display> Display for Painted<'s, T> {
+	fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+		if *self.style == Style::PLAIN {
+			return self.value.fmt(f)
+		}
+
+		self.style.write_prefix(f)?;
+		self.value.fmt(f)?;
+		f.write_str(RESET)
+	}
+}
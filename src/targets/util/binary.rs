@@ -0,0 +1,551 @@
+//! Compact binary encoding of log records for constrained links.
+//!
+//! Instead of formatting the whole text line on the device, each record is encoded as a small frame:
+//! the level and the timestamp (in microseconds) are encoded as varints, the target is replaced by an id
+//! and only the record arguments are formatted. Each frame is [COBS](https://en.wikipedia.org/wiki/Consistent_Overhead_Byte_Stuffing)
+//! encoded and terminated by a zero byte, so the receiver can resynchronize on any frame boundary.
+//!
+//! The first time a target is seen the encoder emits a definition frame binding its id to its name.
+//! A receiver that attaches to the stream later will see the ids of already defined targets instead of
+//! their names, [`BinaryEncoder::reset`](struct.BinaryEncoder.html#method.reset) can be used to make the encoder resend the definitions.
+//!
+//! With the `std` feature, [`BinaryDecoder`](struct.BinaryDecoder.html) and [`decode_stream`](fn.decode_stream.html)
+//! turn the byte stream back into [`LogEntry`](../../../parse/struct.LogEntry.html)s and the standard text lines on the host side.
+//!
+//! ```
+//! use edwardium_logger::targets::util::binary::{BinaryDecoder, BinaryEncoder};
+//!
+//! let record = log::Record::builder()
+//! 	.level(log::Level::Info)
+//! 	.target("app::net")
+//! 	.args(format_args!("connected to {}", "10.0.0.1"))
+//! 	.build();
+//!
+//! let mut bytes: Vec<u8> = Vec::new();
+//! let mut encoder: BinaryEncoder = BinaryEncoder::new();
+//! encoder
+//! 	.encode(
+//! 		&mut bytes,
+//! 		std::time::Duration::from_millis(61_500),
+//! 		&record
+//! 	)
+//! 	.unwrap();
+//!
+//! let mut decoder = BinaryDecoder::new();
+//! let records = decoder.feed(&bytes);
+//! assert_eq!(
+//! 	records[0].as_ref().unwrap().to_string(),
+//! 	"[+001:01.0500][INFO] (app::net) connected to 10.0.0.1"
+//! );
+//! ```
+
+use std::{
+	fmt::{self, Write},
+	time::Duration
+};
+
+use log::Record;
+
+/// Frame defining the name of an interned target.
+///
+/// Layout: `FRAME_TARGET, varint id, name bytes`.
+pub const FRAME_TARGET: u8 = 1;
+/// Frame carrying one record.
+///
+/// Layout: `FRAME_RECORD, varint level, varint timestamp micros, varint target id, [varint len, target bytes if id == 0], message bytes`.
+pub const FRAME_RECORD: u8 = 2;
+
+/// Sink for raw bytes produced by the binary encoder.
+pub trait ByteSink {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), fmt::Error>;
+}
+#[cfg(feature = "std")]
+impl ByteSink for Vec<u8> {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), fmt::Error> {
+		self.extend_from_slice(bytes);
+		Ok(())
+	}
+}
+
+/// Streaming COBS encoder.
+///
+/// Buffers at most one block (254 bytes) before passing it to the sink.
+struct CobsWriter<'s, S: ByteSink> {
+	sink: &'s mut S,
+	block: [u8; 255],
+	len: usize
+}
+impl<'s, S: ByteSink> CobsWriter<'s, S> {
+	fn new(sink: &'s mut S) -> Self {
+		CobsWriter {
+			sink,
+			block: [0; 255],
+			len: 0
+		}
+	}
+
+	fn emit_block(&mut self) -> Result<(), fmt::Error> {
+		self.block[0] = self.len as u8 + 1;
+		self.sink.write_bytes(&self.block[..= self.len])?;
+		self.len = 0;
+
+		Ok(())
+	}
+
+	fn write_byte(&mut self, byte: u8) -> Result<(), fmt::Error> {
+		if byte == 0 {
+			return self.emit_block()
+		}
+
+		self.len += 1;
+		self.block[self.len] = byte;
+		if self.len == 254 {
+			self.emit_block()?;
+		}
+
+		Ok(())
+	}
+
+	fn write_varint(&mut self, mut value: u64) -> Result<(), fmt::Error> {
+		loop {
+			let byte = (value & 0x7F) as u8;
+			value >>= 7;
+
+			if value == 0 {
+				return self.write_byte(byte)
+			}
+			self.write_byte(byte | 0x80)?;
+		}
+	}
+
+	/// Emits the last block and the frame delimiter.
+	fn finish(mut self) -> Result<(), fmt::Error> {
+		self.emit_block()?;
+		self.sink.write_bytes(&[0])
+	}
+}
+impl<'s, S: ByteSink> Write for CobsWriter<'s, S> {
+	fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+		s.bytes().try_for_each(|byte| self.write_byte(byte))
+	}
+}
+
+/// FNV-1a hash used to recognize already interned targets without storing them.
+///
+/// Targets are compared by their hash and length only, two distinct targets of the same length whose hashes
+/// collide would share an id and the later one would be decoded with the name of the first one.
+/// With 64-bit hashes and at most `N` interned targets this is extremely unlikely.
+const fn target_hash(target: &str) -> u64 {
+	let bytes = target.as_bytes();
+
+	let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+	let mut i = 0;
+	while i < bytes.len() {
+		hash ^= bytes[i] as u64;
+		hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+		i += 1;
+	}
+
+	hash
+}
+
+/// Encodes records into COBS-framed binary frames.
+///
+/// Up to `N` distinct targets are interned, records with targets beyond that carry the target name inline.
+pub struct BinaryEncoder<const N: usize = 32> {
+	/// Hash and length of each interned target.
+	targets: [(u64, usize); N],
+	len: usize
+}
+impl<const N: usize> BinaryEncoder<N> {
+	pub const fn new() -> Self {
+		BinaryEncoder {
+			targets: [(0, 0); N],
+			len: 0
+		}
+	}
+
+	/// Forgets all interned targets so that their definitions are sent again.
+	pub fn reset(&mut self) {
+		self.len = 0;
+	}
+
+	/// Returns the id of `target` and whether it still has to be defined.
+	///
+	/// Id `0` means the target could not be interned.
+	fn lookup(&self, key: (u64, usize)) -> (u64, bool) {
+		if let Some(index) = self.targets[.. self.len].iter().position(|&k| k == key) {
+			return (index as u64 + 1, false)
+		}
+
+		if self.len == N {
+			return (0, false)
+		}
+
+		(self.len as u64 + 1, true)
+	}
+
+	/// Encodes `record` into `sink`, preceded by a target definition frame if needed.
+	pub fn encode(
+		&mut self,
+		sink: &mut impl ByteSink,
+		duration_since_start: Duration,
+		record: &Record
+	) -> Result<(), fmt::Error> {
+		let key = (target_hash(record.target()), record.target().len());
+		let (target_id, new) = self.lookup(key);
+
+		if new {
+			let mut frame = CobsWriter::new(sink);
+			frame.write_byte(FRAME_TARGET)?;
+			frame.write_varint(target_id)?;
+			frame.write_str(record.target())?;
+			frame.finish()?;
+
+			// only interned once the definition was written, otherwise it is sent again with the next record
+			self.targets[self.len] = key;
+			self.len += 1;
+		}
+
+		let mut frame = CobsWriter::new(sink);
+		frame.write_byte(FRAME_RECORD)?;
+		frame.write_varint(record.level() as u64)?;
+		frame.write_varint(duration_since_start.as_micros() as u64)?;
+		frame.write_varint(target_id)?;
+		if target_id == 0 {
+			frame.write_varint(record.target().len() as u64)?;
+			frame.write_str(record.target())?;
+		}
+		write!(frame, "{}", record.args())?;
+		frame.finish()
+	}
+}
+impl<const N: usize> Default for BinaryEncoder<N> {
+	fn default() -> Self {
+		BinaryEncoder::new()
+	}
+}
+
+#[cfg(feature = "std")]
+pub use self::decoder::{decode_stream, BinaryDecoder, DecodeError};
+
+#[cfg(feature = "std")]
+mod decoder {
+	use std::{
+		collections::HashMap,
+		fmt::{self, Display, Formatter},
+		io::{self, BufRead, Write},
+		time::Duration
+	};
+
+	use log::Level;
+
+	use super::{FRAME_RECORD, FRAME_TARGET};
+	use crate::{parse::LogEntry, targets::util::TimestampFormat};
+
+	#[derive(Debug, Clone, PartialEq, Eq)]
+	pub enum DecodeError {
+		/// The COBS framing of the frame is broken.
+		InvalidFraming,
+		/// The frame ended before all fields were read.
+		Truncated,
+		/// The frame has an unknown type.
+		UnknownFrame(u8),
+		/// The record level is out of range.
+		InvalidLevel(u64),
+		/// A target name or the message is not valid utf-8.
+		InvalidUtf8
+	}
+	impl Display for DecodeError {
+		fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+			match self {
+				DecodeError::InvalidFraming => write!(f, "invalid COBS framing"),
+				DecodeError::Truncated => write!(f, "truncated frame"),
+				DecodeError::UnknownFrame(kind) => write!(f, "unknown frame type {}", kind),
+				DecodeError::InvalidLevel(level) => write!(f, "invalid level {}", level),
+				DecodeError::InvalidUtf8 => write!(f, "invalid utf-8")
+			}
+		}
+	}
+	impl std::error::Error for DecodeError {}
+
+	fn cobs_decode(frame: &[u8]) -> Result<Vec<u8>, DecodeError> {
+		let mut data = Vec::with_capacity(frame.len());
+
+		let mut position = 0;
+		while position < frame.len() {
+			let code = frame[position] as usize;
+			if code == 0 || position + code > frame.len() {
+				return Err(DecodeError::InvalidFraming)
+			}
+
+			data.extend_from_slice(&frame[position + 1 .. position + code]);
+			position += code;
+
+			if code != 0xFF && position < frame.len() {
+				data.push(0);
+			}
+		}
+
+		Ok(data)
+	}
+
+	struct Reader<'a>(&'a [u8]);
+	impl<'a> Reader<'a> {
+		fn byte(&mut self) -> Result<u8, DecodeError> {
+			let (&byte, rest) = self.0.split_first().ok_or(DecodeError::Truncated)?;
+			self.0 = rest;
+
+			Ok(byte)
+		}
+
+		fn varint(&mut self) -> Result<u64, DecodeError> {
+			let mut value = 0u64;
+
+			for shift in (0 .. 64).step_by(7) {
+				let byte = self.byte()?;
+				value |= ((byte & 0x7F) as u64) << shift;
+
+				if byte & 0x80 == 0 {
+					return Ok(value)
+				}
+			}
+
+			Err(DecodeError::InvalidFraming)
+		}
+
+		fn bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+			if len > self.0.len() {
+				return Err(DecodeError::Truncated)
+			}
+
+			let (bytes, rest) = self.0.split_at(len);
+			self.0 = rest;
+
+			Ok(bytes)
+		}
+
+		fn string(bytes: &[u8]) -> Result<String, DecodeError> {
+			String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+		}
+	}
+
+	/// Host-side decoder for the binary stream.
+	///
+	/// Keeps track of target definitions and of incomplete frames between calls to [`feed`](#method.feed).
+	#[derive(Debug, Default)]
+	pub struct BinaryDecoder {
+		targets: HashMap<u64, String>,
+		pending: Vec<u8>
+	}
+	impl BinaryDecoder {
+		pub fn new() -> Self {
+			Self::default()
+		}
+
+		/// Decodes one frame without the zero delimiter.
+		///
+		/// Returns `None` for frames that don't carry a record.
+		pub fn decode_frame(&mut self, frame: &[u8]) -> Result<Option<LogEntry>, DecodeError> {
+			let data = cobs_decode(frame)?;
+			let mut reader = Reader(&data);
+
+			match reader.byte()? {
+				FRAME_TARGET => {
+					let id = reader.varint()?;
+					let name = Reader::string(reader.0)?;
+					self.targets.insert(id, name);
+
+					Ok(None)
+				}
+				FRAME_RECORD => {
+					let level = match reader.varint()? {
+						1 => Level::Error,
+						2 => Level::Warn,
+						3 => Level::Info,
+						4 => Level::Debug,
+						5 => Level::Trace,
+						level => return Err(DecodeError::InvalidLevel(level))
+					};
+					let duration_since_start = Duration::from_micros(reader.varint()?);
+
+					let target = match reader.varint()? {
+						0 => {
+							let len = reader.varint()? as usize;
+							Reader::string(reader.bytes(len)?)?
+						}
+						id => match self.targets.get(&id) {
+							Some(name) => name.clone(),
+							None => format!("#{}", id)
+						}
+					};
+					let message = Reader::string(reader.0)?;
+
+					Ok(Some(LogEntry {
+						duration_since_start,
+						timestamp_format: TimestampFormat::DEFAULT,
+						level,
+						fields: Vec::new(),
+						target,
+						message
+					}))
+				}
+				kind => Err(DecodeError::UnknownFrame(kind))
+			}
+		}
+
+		/// Feeds a chunk of the byte stream into the decoder and returns the records completed by it.
+		///
+		/// Invalid frames are returned as errors in stream order and decoding continues with the next frame.
+		pub fn feed(&mut self, bytes: &[u8]) -> Vec<Result<LogEntry, DecodeError>> {
+			self.pending.extend_from_slice(bytes);
+
+			let mut records = Vec::new();
+			while let Some(end) = self.pending.iter().position(|&b| b == 0) {
+				let frame: Vec<u8> = self.pending.drain(..= end).collect();
+				if frame.len() == 1 {
+					continue
+				}
+
+				if let Some(record) = self.decode_frame(&frame[.. frame.len() - 1]).transpose() {
+					records.push(record);
+				}
+			}
+
+			records
+		}
+	}
+
+	/// Reads the binary stream from `input` and writes the standard text lines into `output`.
+	///
+	/// Invalid frames are reported in the output and skipped.
+	pub fn decode_stream(mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+		let mut decoder = BinaryDecoder::new();
+		let mut frame = Vec::new();
+
+		loop {
+			frame.clear();
+			if input.read_until(0, &mut frame)? == 0 {
+				return Ok(())
+			}
+			if frame.last() != Some(&0) {
+				// incomplete frame at the end of the input
+				return Ok(())
+			}
+			if frame.len() == 1 {
+				continue
+			}
+
+			match decoder.decode_frame(&frame[.. frame.len() - 1]) {
+				Ok(None) => (),
+				Ok(Some(record)) => writeln!(output, "{}", record)?,
+				Err(err) => writeln!(output, "<{}>", err)?
+			}
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use std::time::Duration;
+
+		use log::{Level, Record};
+
+		use super::{BinaryDecoder, DecodeError};
+		use crate::targets::util::binary::{BinaryEncoder, ByteSink};
+
+		fn encode(encoder: &mut BinaryEncoder, message: &str) -> Vec<u8> {
+			let mut bytes = Vec::new();
+			encoder
+				.encode(
+					&mut bytes,
+					Duration::from_millis(1500),
+					&Record::builder()
+						.level(Level::Info)
+						.target("app")
+						.args(format_args!("{}", message))
+						.build()
+				)
+				.unwrap();
+
+			bytes
+		}
+
+		fn messages(
+			results: Vec<Result<crate::parse::LogEntry, DecodeError>>
+		) -> Vec<Result<String, DecodeError>> {
+			results
+				.into_iter()
+				.map(|result| result.map(|record| record.message))
+				.collect()
+		}
+
+		#[test]
+		fn failed_definition_is_sent_again() {
+			struct Failing;
+			impl ByteSink for Failing {
+				fn write_bytes(&mut self, _bytes: &[u8]) -> Result<(), std::fmt::Error> {
+					Err(std::fmt::Error)
+				}
+			}
+
+			let mut encoder = BinaryEncoder::new();
+			let record = Record::builder()
+				.level(Level::Info)
+				.target("app")
+				.args(format_args!("lost"))
+				.build();
+			assert!(encoder
+				.encode(&mut Failing, Duration::ZERO, &record)
+				.is_err());
+
+			let mut decoder = BinaryDecoder::new();
+			let records = decoder.feed(&encode(&mut encoder, "kept"));
+			assert_eq!(records[0].as_ref().unwrap().target, "app");
+		}
+
+		#[test]
+		fn feed_keeps_records_around_invalid_frames() {
+			let mut encoder = BinaryEncoder::new();
+			let mut bytes = encode(&mut encoder, "first");
+			// unknown frame type
+			bytes.extend_from_slice(&[0x02, 0x07, 0x00]);
+			bytes.extend(encode(&mut encoder, "second"));
+
+			let mut decoder = BinaryDecoder::new();
+			assert_eq!(
+				messages(decoder.feed(&bytes)),
+				[
+					Ok("first".to_string()),
+					Err(DecodeError::UnknownFrame(7)),
+					Ok("second".to_string())
+				]
+			);
+		}
+
+		#[test]
+		fn feed_reports_truncated_and_broken_frames() {
+			let mut decoder = BinaryDecoder::new();
+			// record frame that ends after the level
+			assert_eq!(
+				decoder.feed(&[0x03, 0x02, 0x03, 0x00]),
+				[Err(DecodeError::Truncated)]
+			);
+			// code byte pointing past the end of the frame
+			assert_eq!(
+				decoder.feed(&[0x05, 0x02, 0x00]),
+				[Err(DecodeError::InvalidFraming)]
+			);
+		}
+
+		#[test]
+		fn feed_decodes_frames_split_across_calls() {
+			let bytes = encode(&mut BinaryEncoder::new(), "split");
+			let (head, tail) = bytes.split_at(bytes.len() - 3);
+
+			let mut decoder = BinaryDecoder::new();
+			assert!(decoder.feed(head).is_empty());
+			let records = decoder.feed(tail);
+			assert_eq!(messages(records), [Ok("split".to_string())]);
+		}
+	}
+}
@@ -0,0 +1,165 @@
+//! Shared rendering of records.
+//!
+//! A target that renders records in one of the layouts provided by this crate describes it using a
+//! [`Layout`](struct.Layout.html) and receives the rendered line through
+//! [`Target::write_rendered`](../../../target/trait.Target.html#method.write_rendered).
+//! When a tuple of targets is written to, each distinct layout is rendered only once per record
+//! into a reusable thread-local buffer and all targets with that layout receive the same bytes.
+
+use std::{
+	cell::RefCell,
+	io::{self, Write},
+	time::Duration
+};
+
+use log::Record;
+
+#[cfg(feature = "colored_logline")]
+use super::colored_logline::{ColorTheme, ColoredLogLine};
+use super::{pattern::Pattern, LineFields, LogLine, Timestamp, TimestampFormat};
+
+/// How a layout renders the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutStyle<'a> {
+	/// [`LogLine`](../struct.LogLine.html)
+	Plain,
+	/// [`ColoredLogLine`](../colored_logline/struct.ColoredLogLine.html) with a theme
+	#[cfg(feature = "colored_logline")]
+	Colored(&'a ColorTheme),
+	/// [`Pattern`](../pattern/struct.Pattern.html)
+	Pattern(&'a Pattern)
+}
+
+/// Description of a rendered line.
+///
+/// Two targets with equal layouts would render any record into the same bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout<'a> {
+	pub style: LayoutStyle<'a>,
+	/// Duration rendered as the timestamp, usually the duration since start.
+	pub timestamp: Duration,
+	pub timestamp_format: TimestampFormat,
+	pub fields: LineFields
+}
+impl<'a> Layout<'a> {
+	/// Plain `LogLine` layout with the default timestamp format and no fields.
+	pub const fn plain(timestamp: Duration) -> Self {
+		Layout {
+			style: LayoutStyle::Plain,
+			timestamp,
+			timestamp_format: TimestampFormat::DEFAULT,
+			fields: LineFields::NONE
+		}
+	}
+
+	/// Renders `record` followed by a newline.
+	pub fn render(&self, record: &Record, output: &mut impl Write) -> io::Result<()> {
+		let timestamp = Timestamp::from(self.timestamp).with_format(self.timestamp_format);
+
+		match self.style {
+			LayoutStyle::Plain => writeln!(
+				output,
+				"{}",
+				LogLine::new(timestamp, record).with_fields(self.fields, record)
+			),
+			#[cfg(feature = "colored_logline")]
+			LayoutStyle::Colored(theme) => writeln!(
+				output,
+				"{}",
+				ColoredLogLine::new(timestamp, record)
+					.with_fields(self.fields, record)
+					.with_theme(theme)
+			),
+			LayoutStyle::Pattern(pattern) => writeln!(
+				output,
+				"{}",
+				pattern
+					.render(self.timestamp, record)
+					.with_timestamp_format(self.timestamp_format)
+			)
+		}
+	}
+}
+
+/// Maximum number of distinct layouts remembered per record, the size of the largest `Targets` tuple.
+const CACHED_LAYOUTS: usize = 8;
+
+/// Buffers larger than this are not kept for the next record.
+const MAX_RETAINED_CAPACITY: usize = 64 * 1024;
+
+/// Number of buffers kept per thread, enough for targets nesting other targets.
+const MAX_RETAINED_BUFFERS: usize = 4;
+
+thread_local! {
+	static RENDER_BUFFERS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Lines of one record rendered in different layouts.
+pub struct RenderCache<'a, 'b> {
+	buffer: &'b mut Vec<u8>,
+	lines: [Option<(Layout<'a>, usize, usize)>; CACHED_LAYOUTS]
+}
+impl<'a, 'b> RenderCache<'a, 'b> {
+	/// Runs `f` with an empty cache backed by a reused thread-local buffer.
+	///
+	/// Nested calls, for example from a target that writes to other targets, each take their own buffer.
+	pub fn with<R>(f: impl FnOnce(&mut RenderCache<'a, '_>) -> R) -> R {
+		let mut buffer = RENDER_BUFFERS
+			.try_with(|buffers| buffers.borrow_mut().pop())
+			.ok()
+			.flatten()
+			.unwrap_or_default();
+
+		buffer.clear();
+		let result = f(&mut RenderCache {
+			buffer: &mut buffer,
+			lines: [None; CACHED_LAYOUTS]
+		});
+
+		buffer.clear();
+		if buffer.capacity() > MAX_RETAINED_CAPACITY {
+			buffer.shrink_to(MAX_RETAINED_CAPACITY);
+		}
+		let _ = RENDER_BUFFERS.try_with(|buffers| {
+			let mut buffers = buffers.borrow_mut();
+			if buffers.len() < MAX_RETAINED_BUFFERS {
+				buffers.push(buffer);
+			}
+		});
+
+		result
+	}
+
+	/// Returns `record` rendered in `layout`, rendering it only if no line with an equal layout was rendered yet.
+	pub fn line(&mut self, layout: Layout<'a>, record: &Record) -> io::Result<&[u8]> {
+		let cached = self
+			.lines
+			.iter()
+			.flatten()
+			.find(|(cached, ..)| *cached == layout);
+		if let Some(&(_, start, end)) = cached {
+			return Ok(&self.buffer[start .. end])
+		}
+
+		let start = self.buffer.len();
+		layout.render(record, self.buffer)?;
+		let end = self.buffer.len();
+
+		if let Some(slot) = self.lines.iter_mut().find(|slot| slot.is_none()) {
+			*slot = Some((layout, start, end));
+		}
+
+		Ok(&self.buffer[start .. end])
+	}
+}
+
+/// Renders `record` in `layout` into the thread-local buffer and passes it to `write`.
+///
+/// Meant for implementing `Target::write` of targets that have a layout.
+pub fn write_with_layout<E: From<io::Error>>(
+	layout: Layout,
+	record: &Record,
+	write: impl FnOnce(&[u8]) -> Result<(), E>
+) -> Result<(), E> {
+	RenderCache::with(|cache| write(cache.line(layout, record)?))
+}
@@ -0,0 +1,445 @@
+//! Runtime configurable line layout.
+//!
+//! A [`Pattern`](struct.Pattern.html) is parsed once from a string such as
+//! `"{time:rfc3339} {level:5} [{thread}] {target}: {message} ({file}:{line})"`
+//! and then renders records without allocating.
+//!
+//! Placeholders have the form `{field}` or `{field:spec}`, `{{` and `}}` are literal braces.
+//!
+//! | Field | Value |
+//! |-------|-------|
+//! | `elapsed` | time since the logger start, in the target's `TimestampFormat` (`+mmm:ss.mmmm` by default) |
+//! | `time` | wall clock time, spec is `rfc3339` (default, UTC with milliseconds) or `unix` (seconds with milliseconds) |
+//! | `level` | record level |
+//! | `target` | record target |
+//! | `message` | formatted record arguments |
+//! | `module` | record module path |
+//! | `file`, `line` | record source location |
+//! | `thread` | current thread name, or its id if it has no name |
+//! | `thread_id` | current thread id |
+//! | `pid` | process id |
+//!
+//! Values that are not available are rendered as `?`. For all fields except `time` the spec is a minimum width
+//! optionally prefixed by an alignment: `<` (default), `>` or `^`.
+//!
+//! ```
+//! use edwardium_logger::targets::util::pattern::Pattern;
+//!
+//! let pattern = Pattern::parse("{level:>5} {target}: {message} ({file}:{line})").unwrap();
+//!
+//! let record = log::Record::builder()
+//! 	.level(log::Level::Info)
+//! 	.target("app")
+//! 	.file(Some("src/main.rs"))
+//! 	.line(Some(12))
+//! 	.args(format_args!("hello"))
+//! 	.build();
+//! assert_eq!(
+//! 	pattern
+//! 		.render(std::time::Duration::ZERO, &record)
+//! 		.to_string(),
+//! 	" INFO app: hello (src/main.rs:12)"
+//! );
+//! ```
+
+use std::{
+	cell::RefCell,
+	fmt::{self, Display, Formatter, Write},
+	time::{Duration, SystemTime, UNIX_EPOCH}
+};
+
+use log::Record;
+
+use super::{Timestamp, TimestampFormat};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+	Elapsed,
+	Time(TimeFormat),
+	Level,
+	Target,
+	Message,
+	Module,
+	File,
+	Line,
+	Thread,
+	ThreadId,
+	Pid
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeFormat {
+	Rfc3339,
+	Unix
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+	Left,
+	Right,
+	Center
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+	Literal(String),
+	Field {
+		field: Field,
+		width: usize,
+		align: Align
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+	/// A `{` without matching `}`.
+	Unclosed,
+	/// A `}` without matching `{`.
+	Unopened,
+	UnknownField(String),
+	InvalidSpec(String)
+}
+impl Display for PatternError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			PatternError::Unclosed => write!(f, "unclosed placeholder"),
+			PatternError::Unopened => write!(f, "unmatched }}"),
+			PatternError::UnknownField(field) => write!(f, "unknown field {}", field),
+			PatternError::InvalidSpec(spec) => write!(f, "invalid spec {}", spec)
+		}
+	}
+}
+impl std::error::Error for PatternError {}
+
+/// Parsed line layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+	pieces: Vec<Piece>
+}
+impl Pattern {
+	pub fn parse(pattern: &str) -> Result<Self, PatternError> {
+		let mut pieces = Vec::new();
+		let mut literal = String::new();
+
+		let mut rest = pattern;
+		while let Some(index) = rest.find(['{', '}']) {
+			literal.push_str(&rest[.. index]);
+			let brace = &rest[index .. index + 1];
+			rest = &rest[index + 1 ..];
+
+			if let Some(after) = rest.strip_prefix(brace) {
+				literal.push_str(brace);
+				rest = after;
+				continue
+			}
+			if brace == "}" {
+				return Err(PatternError::Unopened)
+			}
+
+			let (placeholder, after) = rest.split_once('}').ok_or(PatternError::Unclosed)?;
+			rest = after;
+
+			if !literal.is_empty() {
+				pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+			}
+			pieces.push(Self::parse_placeholder(placeholder)?);
+		}
+		literal.push_str(rest);
+		if !literal.is_empty() {
+			pieces.push(Piece::Literal(literal));
+		}
+
+		Ok(Pattern { pieces })
+	}
+
+	fn parse_placeholder(placeholder: &str) -> Result<Piece, PatternError> {
+		let (name, spec) = match placeholder.split_once(':') {
+			Some((name, spec)) => (name, Some(spec)),
+			None => (placeholder, None)
+		};
+
+		let field = match name {
+			"elapsed" => Field::Elapsed,
+			"time" => {
+				let format = match spec {
+					None | Some("rfc3339") => TimeFormat::Rfc3339,
+					Some("unix") => TimeFormat::Unix,
+					Some(spec) => return Err(PatternError::InvalidSpec(spec.to_string()))
+				};
+
+				return Ok(Piece::Field {
+					field: Field::Time(format),
+					width: 0,
+					align: Align::Left
+				})
+			}
+			"level" => Field::Level,
+			"target" => Field::Target,
+			"message" => Field::Message,
+			"module" => Field::Module,
+			"file" => Field::File,
+			"line" => Field::Line,
+			"thread" => Field::Thread,
+			"thread_id" => Field::ThreadId,
+			"pid" => Field::Pid,
+			name => return Err(PatternError::UnknownField(name.to_string()))
+		};
+
+		let spec = spec.unwrap_or("");
+		let (align, width) = match spec.as_bytes().first() {
+			Some(b'<') => (Align::Left, &spec[1 ..]),
+			Some(b'>') => (Align::Right, &spec[1 ..]),
+			Some(b'^') => (Align::Center, &spec[1 ..]),
+			_ => (Align::Left, spec)
+		};
+		let width = match width {
+			"" => 0,
+			width => width
+				.parse()
+				.map_err(|_| PatternError::InvalidSpec(spec.to_string()))?
+		};
+
+		Ok(Piece::Field {
+			field,
+			width,
+			align
+		})
+	}
+
+	/// Returns a `Display` rendering `record` using this pattern.
+	pub fn render<'a>(
+		&'a self,
+		duration_since_start: Duration,
+		record: &'a Record<'a>
+	) -> PatternLine<'a> {
+		PatternLine {
+			pattern: self,
+			duration_since_start,
+			timestamp_format: TimestampFormat::DEFAULT,
+			record
+		}
+	}
+}
+impl std::str::FromStr for Pattern {
+	type Err = PatternError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Pattern::parse(s)
+	}
+}
+
+/// Record rendered using a `Pattern`.
+pub struct PatternLine<'a> {
+	pattern: &'a Pattern,
+	duration_since_start: Duration,
+	timestamp_format: TimestampFormat,
+	record: &'a Record<'a>
+}
+impl<'a> PatternLine<'a> {
+	/// Sets how `{elapsed}` is rendered, `TimestampFormat::DEFAULT` by default.
+	pub const fn with_timestamp_format(mut self, format: TimestampFormat) -> Self {
+		self.timestamp_format = format;
+		self
+	}
+
+	fn write_field(&self, f: &mut dyn Write, field: Field) -> fmt::Result {
+		let record = self.record;
+
+		match field {
+			Field::Elapsed => write!(
+				f,
+				"{}",
+				Timestamp::from(self.duration_since_start).with_format(self.timestamp_format)
+			),
+			Field::Time(format) => write_time(f, format, SystemTime::now()),
+			Field::Level => write!(f, "{}", record.level()),
+			Field::Target => f.write_str(record.target()),
+			Field::Message => write!(f, "{}", record.args()),
+			Field::Module => f.write_str(record.module_path().unwrap_or("?")),
+			Field::File => f.write_str(record.file().unwrap_or("?")),
+			Field::Line => match record.line() {
+				Some(line) => write!(f, "{}", line),
+				None => f.write_str("?")
+			},
+			Field::Thread => {
+				let thread = std::thread::current();
+				match thread.name() {
+					Some(name) => f.write_str(name),
+					None => write!(f, "{:?}", thread.id())
+				}
+			}
+			Field::ThreadId => write!(f, "{:?}", std::thread::current().id()),
+			Field::Pid => write!(f, "{}", std::process::id())
+		}
+	}
+}
+impl<'a> Display for PatternLine<'a> {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		for piece in self.pattern.pieces.iter() {
+			match *piece {
+				Piece::Literal(ref literal) => f.write_str(literal)?,
+				Piece::Field {
+					field, width: 0, ..
+				} => self.write_field(f, field)?,
+				Piece::Field {
+					field,
+					width,
+					align
+				} => {
+					// the field is rendered once and padded from the buffer
+					let mut buffer = FIELD_BUFFER.try_with(RefCell::take).unwrap_or_default();
+					buffer.clear();
+
+					let result = self.write_field(&mut buffer, field).and_then(|()| {
+						let padding = width.saturating_sub(buffer.chars().count());
+						let (before, after) = match align {
+							Align::Left => (0, padding),
+							Align::Right => (padding, 0),
+							Align::Center => (padding / 2, padding - padding / 2)
+						};
+
+						write_padding(f, before)?;
+						f.write_str(&buffer)?;
+						write_padding(f, after)
+					});
+
+					if buffer.capacity() <= MAX_RETAINED_CAPACITY {
+						let _ = FIELD_BUFFER.try_with(|retained| retained.replace(buffer));
+					}
+					result?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Padded fields larger than this don't keep their buffer for the next record.
+const MAX_RETAINED_CAPACITY: usize = 4 * 1024;
+
+thread_local! {
+	/// Buffer padded fields are rendered into, taken while in use so that nested renders get their own.
+	static FIELD_BUFFER: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+fn write_padding(f: &mut Formatter, count: usize) -> fmt::Result {
+	(0 .. count).try_for_each(|_| f.write_char(' '))
+}
+
+fn write_time(f: &mut dyn Write, format: TimeFormat, time: SystemTime) -> fmt::Result {
+	let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+	let secs = since_epoch.as_secs();
+	let millis = since_epoch.subsec_millis();
+
+	match format {
+		TimeFormat::Unix => write!(f, "{}.{:03}", secs, millis),
+		TimeFormat::Rfc3339 => {
+			let (year, month, day) = civil_from_days((secs / 86400) as i64);
+			let secs_of_day = secs % 86400;
+
+			write!(
+				f,
+				"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+				year,
+				month,
+				day,
+				secs_of_day / 3600,
+				secs_of_day / 60 % 60,
+				secs_of_day % 60,
+				millis
+			)
+		}
+	}
+}
+
+/// Converts days since the unix epoch into a (year, month, day) date in the proleptic Gregorian calendar.
+///
+/// Algorithm from <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+	let z = days + 719_468;
+	let era = z.div_euclid(146_097);
+	let doe = z.rem_euclid(146_097);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+	let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+	(year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		cell::Cell,
+		fmt::{self, Display, Formatter},
+		time::Duration
+	};
+
+	use log::{Level, Record};
+
+	use super::Pattern;
+	use crate::targets::util::{LargestUnit, Precision, TimestampFormat};
+
+	fn render(pattern: &str, message: fmt::Arguments) -> String {
+		let record = Record::builder()
+			.level(Level::Warn)
+			.target("app")
+			.args(message)
+			.build();
+
+		Pattern::parse(pattern)
+			.unwrap()
+			.render(Duration::ZERO, &record)
+			.to_string()
+	}
+
+	#[test]
+	fn pads_fields_to_width() {
+		assert_eq!(render("[{level:6}]", format_args!("")), "[WARN  ]");
+		assert_eq!(render("[{level:>6}]", format_args!("")), "[  WARN]");
+		assert_eq!(render("[{level:^7}]", format_args!("")), "[ WARN  ]");
+		assert_eq!(render("[{target:2}]", format_args!("")), "[app]");
+		assert_eq!(render("[{message:4}]", format_args!("é")), "[é   ]");
+	}
+
+	#[test]
+	fn renders_padded_fields_once() {
+		struct Counted<'a>(&'a Cell<usize>);
+		impl Display for Counted<'_> {
+			fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+				self.0.set(self.0.get() + 1);
+				f.write_str("counted")
+			}
+		}
+
+		let count = Cell::new(0);
+		assert_eq!(
+			render("{message:>9}|", format_args!("{}", Counted(&count))),
+			"  counted|"
+		);
+		assert_eq!(count.get(), 1);
+	}
+
+	#[test]
+	fn renders_elapsed_in_timestamp_format() {
+		let pattern = Pattern::parse("{elapsed}").unwrap();
+		let record = Record::builder().args(format_args!("")).build();
+		let elapsed = Duration::from_micros(3_723_004_005);
+
+		assert_eq!(pattern.render(elapsed, &record).to_string(), "+062:03.0004");
+		assert_eq!(
+			pattern
+				.render(elapsed, &record)
+				.with_timestamp_format(
+					TimestampFormat::DEFAULT
+						.with_largest_unit(LargestUnit::Hours)
+						.with_precision(Precision::Micros)
+				)
+				.to_string(),
+			"+01:02:03.004005"
+		);
+	}
+}
@@ -1,4 +1,106 @@
+/// Source of the durations since start that are passed to targets.
+///
+/// The logger holds a clock instance, so a clock can carry state such as a reference to a timer peripheral.
+///
+/// Every [`Timing`](trait.Timing.html) implementation is a clock measuring the time since the instance was created,
+/// so `std::time::Instant` and `DummyTiming` can be used directly.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use edwardium_logger::timing::Clock;
+///
+/// struct TickCounter {
+/// 	ticks: fn() -> u32
+/// }
+/// impl Clock for TickCounter {
+/// 	fn elapsed(&self) -> Duration {
+/// 		Duration::from_millis((self.ticks)() as u64)
+/// 	}
+/// }
+///
+/// let clock = TickCounter { ticks: || 1500 };
+/// assert_eq!(clock.elapsed(), Duration::from_millis(1500));
+/// ```
+pub trait Clock {
+	/// Returns duration elapsed since the start of the clock.
+	fn elapsed(&self) -> std::time::Duration;
+
+	/// Called when the logger holding this clock is initialized.
+	fn init(&self) {}
+}
+impl<T: Timing> Clock for T {
+	fn elapsed(&self) -> std::time::Duration {
+		T::now().duration_since(self)
+	}
+}
+
+/// Clock backed by a function returning the elapsed duration.
+///
+/// Can be used to wrap a timer without writing a `Clock` implementation.
+pub struct FnClock<F: Fn() -> std::time::Duration>(pub F);
+impl<F: Fn() -> std::time::Duration> Clock for FnClock<F> {
+	fn elapsed(&self) -> std::time::Duration {
+		(self.0)()
+	}
+}
+
+/// Deterministic clock that only moves when told to.
+///
+/// Clones share the same time, so a test can keep one handle and pass another one to the logger.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use edwardium_logger::timing::{Clock, ManualClock};
+///
+/// let clock = ManualClock::new();
+/// let logger_clock = clock.clone();
+///
+/// clock.advance(Duration::from_millis(250));
+/// assert_eq!(logger_clock.elapsed(), Duration::from_millis(250));
+///
+/// clock.set(Duration::from_secs(61));
+/// assert_eq!(logger_clock.elapsed(), Duration::from_secs(61));
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+	nanos: std::sync::Arc<std::sync::atomic::AtomicU64>
+}
+#[cfg(feature = "std")]
+impl ManualClock {
+	/// Creates a new clock at zero.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Moves the clock forward by `duration`.
+	pub fn advance(&self, duration: std::time::Duration) {
+		self.nanos.fetch_add(
+			duration.as_nanos() as u64,
+			std::sync::atomic::Ordering::SeqCst
+		);
+	}
+
+	/// Sets the elapsed duration of the clock.
+	pub fn set(&self, duration: std::time::Duration) {
+		self.nanos.store(
+			duration.as_nanos() as u64,
+			std::sync::atomic::Ordering::SeqCst
+		);
+	}
+}
+#[cfg(feature = "std")]
+impl Clock for ManualClock {
+	fn elapsed(&self) -> std::time::Duration {
+		std::time::Duration::from_nanos(self.nanos.load(std::sync::atomic::Ordering::SeqCst))
+	}
+}
+
 /// Trait used to implement custom timing in `no_std` crates where `std::time::Instant` is not available.
+///
+/// Types implementing this trait can be used as a [`Clock`](trait.Clock.html) that measures time since the instance was created.
 pub trait Timing {
 	/// Returns a new reference point in time.
 	fn now() -> Self
@@ -34,38 +136,48 @@ impl Timing for std::time::Instant {
 	}
 }
 
-// YOLO
-// Apparently we can't just crate a "zero" Instant, not even using MaybeUninit because it's not const.
-// But we can instead implement Timing for Option<Instant>, though it becomes ugly in the `duration_since` method.
-// This is an example of how such workaround might look. It's probably better not to include it in the crate.
-//
-// ```
-// static mut LOGGER: edwardium_logger::Logger<
-// 	edwardium_logger::targets::stderr::StderrTarget,
-// 	[edwardium_logger::targets::stderr::StderrTarget; 1],
-// 	Option<std::time::Instant>
-// > = edwardium_logger::Logger {
-// 	targets: [edwardium_logger::targets::stderr::StderrTarget::new(
-// 		log::Level::Trace
-// 	)],
-// 	start: None,
-// 	ghost: std::marker::PhantomData
-// };
-//
-// unsafe {
-// 	*LOGGER.start_mut() = Some(std::time::Instant::now());
-// 	LOGGER.init_static();
-// }
-// ```
-// #[cfg(feature = "std")]
-// impl Timing for Option<std::time::Instant> {
-// fn now() -> Self {
-// Some(
-// std::time::Instant::now()
-// )
-// }
-//
-// fn duration_since(&self, other: &Self) -> std::time::Duration {
-// self.as_ref().unwrap().duration_since(other.unwrap())
-// }
-// }
+/// Start `Instant` captured on first use or when the logger is initialized.
+///
+/// `std::time::Instant` can't be created in a `const` context, so this is the clock to use in a `static` logger
+/// to get real timestamps.
+///
+/// ```
+/// use edwardium_logger::{targets::stderr::StderrTarget, timing::LazyInstant, Logger};
+///
+/// static LOGGER: Logger<StderrTarget, LazyInstant> = Logger::new(
+/// 	StderrTarget::new(
+/// 		log::Level::Trace,
+/// 		edwardium_logger::targets::util::ignore_list::IgnoreList::EMPTY_PATTERNS
+/// 	),
+/// 	LazyInstant::new()
+/// );
+/// LOGGER.init_static().expect("Could not initialize logger");
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct LazyInstant {
+	start: std::sync::OnceLock<std::time::Instant>
+}
+#[cfg(feature = "std")]
+impl LazyInstant {
+	pub const fn new() -> Self {
+		LazyInstant {
+			start: std::sync::OnceLock::new()
+		}
+	}
+
+	/// Returns the start instant, capturing it now if it wasn't captured yet.
+	pub fn start(&self) -> std::time::Instant {
+		*self.start.get_or_init(std::time::Instant::now)
+	}
+}
+#[cfg(feature = "std")]
+impl Clock for LazyInstant {
+	fn elapsed(&self) -> std::time::Duration {
+		self.start().elapsed()
+	}
+
+	fn init(&self) {
+		self.start();
+	}
+}